@@ -14,8 +14,12 @@ def read_chain(filename):
     chain = []
 
     with open(filename) as file:
-        for line in file:
-            parts = line.split() 
+        lines = [line.split('#')[0].split() for line in file]
+        lines = [parts for parts in lines if parts]
+        if lines and lines[0][0] == 'msp-chain':
+            # Skip the header and drop the coefficient at the end of each row.
+            lines = [parts[:-1] for parts in lines[3:]]
+        for parts in lines:
             chain.append([int(x) for x in parts])
 
     return chain
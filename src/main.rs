@@ -18,7 +18,141 @@ fn load_current(mesh: Rc<Mesh>, path: &str) -> Rc<Chain> {
     }
 }
 
+/// `msp validate <mesh>`: prints the topology report of a mesh.
+fn validate(path: &str) {
+    let mesh = match Mesh::load(path) {
+        Ok(m) => m,
+        Err(e) => {
+            std::eprintln!("Error loading mesh: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    let report = mesh.validate();
+    println!("{}", report);
+    if !report.is_valid() { std::process::exit(1); }
+}
+
+/// `msp svg <mesh> <output> [--plane xy|xz|yz] [--size N] [--chain <file>]... [currents...]`:
+/// draws a planar mesh with the given chains (e.g. the `median.txt`, `decomp_r*.txt` and
+/// `decomp_s*.txt` files written by the main pipeline) and currents on top of it. 2-chains are
+/// drawn first so that they do not hide the lines.
+fn svg(args: &[String]) -> Result<(), String> {
+    const COLOURS: [&str; 5] = ["#1f77b4", "#ff7f0e", "#2ca02c", "#d62728", "#9467bd"];
+
+    let mut plane = AxisPlane::XZ;
+    let mut size = None;
+    let mut files = Vec::new();
+    let mut chain_files = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--plane" => plane = match args.next().map(|s| s.as_str()) {
+                Some("xy") => AxisPlane::XY,
+                Some("xz") => AxisPlane::XZ,
+                Some("yz") => AxisPlane::YZ,
+                other => return Err(format!("invalid plane {:?}", other)),
+            },
+            "--size" => size = Some(args.next().and_then(|s| s.parse::<f64>().ok()).ok_or("invalid size")?),
+            "--chain" => chain_files.push(args.next().ok_or("missing chain file")?.as_str()),
+            _ => files.push(arg.as_str()),
+        }
+    }
+    if files.len() < 2 { return Err("expected a mesh and an output file".to_string()); }
+
+    let mesh = Rc::new(Mesh::load(files[0]).map_err(|e| format!("error loading mesh: {}", e))?);
+    let mut chains = Vec::new();
+    for path in chain_files.iter() {
+        chains.push(Chain::load(mesh.clone(), path).map_err(|e| format!("error loading chain {}: {}", path, e))?);
+    }
+    for path in files[2..].iter() {
+        let current = Current::load(mesh.clone(), path).map_err(|e| format!("error loading current: {}", e))?;
+        chains.push(current.as_chain()?);
+    }
+
+    let mut plot = SvgPlot::new(&mesh, plane);
+    if let Some(size) = size { plot = plot.size(size); }
+    let order = chains.iter().enumerate().filter(|(_, c)| c.dim == 2)
+        .chain(chains.iter().enumerate().filter(|(_, c)| c.dim != 2));
+    for (i, chain) in order {
+        plot = plot.add_chain(chain, COLOURS[i % COLOURS.len()]);
+    }
+    plot.save(files[1]).map_err(|e| format!("error saving image: {}", e))
+}
+
+/// `msp generate <grid|icosphere|torus|cylinder> <output> <parameters...>`: writes a procedural
+/// mesh, in the format given by the extension of `output`.
+fn generate(args: &[String]) -> Result<(), String> {
+    use prelude::generate::{cylinder, grid, icosphere, torus, Diagonal};
+
+    let mut diagonal = Diagonal::Alternating;
+    let mut positional = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--diagonal" => diagonal = match args.next().map(|s| s.as_str()) {
+                Some("forward") => Diagonal::Forward,
+                Some("backward") => Diagonal::Backward,
+                Some("alternating") => Diagonal::Alternating,
+                Some("cross") => Diagonal::Cross,
+                other => return Err(format!("invalid diagonal {:?}", other)),
+            },
+            _ => positional.push(arg.as_str()),
+        }
+    }
+    if positional.len() < 2 { return Err("expected a shape and an output file".to_string()); }
+    let (shape, output, params) = (positional[0], positional[1], &positional[2..]);
+
+    let expected = if shape == "icosphere" { 2 } else { 4 };
+    if params.len() != expected { return Err(format!("{} takes {} parameters, got {}", shape, expected, params.len())); }
+    let real = |i: usize| params[i].parse::<f64>().map_err(|_| format!("invalid number '{}'", params[i]));
+    let count = |i: usize, min: usize| match params[i].parse::<usize>() {
+        Ok(n) if n >= min => Ok(n),
+        _ => Err(format!("expected a count of at least {}, got '{}'", min, params[i])),
+    };
+
+    let mesh = match shape {
+        "grid" => grid(real(0)?, real(1)?, count(2, 1)?, count(3, 1)?, diagonal),
+        "icosphere" => icosphere(real(0)?, count(1, 0)?),
+        "torus" => torus(real(0)?, real(1)?, count(2, 3)?, count(3, 3)?),
+        "cylinder" => cylinder(real(0)?, real(1)?, count(2, 3)?, count(3, 1)?),
+        _ => return Err(format!("unknown shape '{}'", shape)),
+    };
+    mesh.save(output).map_err(|e| format!("error saving mesh: {}", e))
+}
+
 fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    if args.get(1).map(|s| s.as_str()) == Some("generate") {
+        if let Err(e) = generate(&args[2..]) {
+            std::eprintln!("Error: {}", e);
+            std::eprintln!("Usage: {} generate grid <output> <width> <depth> <nx> <nz> [--diagonal forward|backward|alternating|cross]", args[0]);
+            std::eprintln!("       {} generate icosphere <output> <radius> <subdivisions>", args[0]);
+            std::eprintln!("       {} generate torus <output> <major radius> <minor radius> <segments> <rings>", args[0]);
+            std::eprintln!("       {} generate cylinder <output> <radius> <height> <segments> <rings>", args[0]);
+            std::process::exit(1);
+        }
+        return;
+    }
+    if args.get(1).map(|s| s.as_str()) == Some("svg") {
+        if let Err(e) = svg(&args[2..]) {
+            std::eprintln!("Error: {}", e);
+            std::eprintln!("Usage: {} svg <mesh> <output> [--plane xy|xz|yz] [--size N] [--chain <file>]... [currents...]", args[0]);
+            std::process::exit(1);
+        }
+        return;
+    }
+    if args.get(1).map(|s| s.as_str()) == Some("validate") {
+        match args.get(2) {
+            Some(path) => validate(path),
+            None => {
+                std::eprintln!("Usage: {} validate <mesh>", args[0]);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
     let mut mesh = Mesh::load("test.off").unwrap();
     mesh.orient().unwrap();
     println!("edges = {:?}", mesh.edges);
@@ -64,7 +198,7 @@ fn main() {
         }
     };
 
-    println!("Result: {}", result.median);
+    println!("Result (mu = {}, lambda = {}, alpha = {:?}): {}", result.mu, result.lambda, result.alpha, result.median.summary());
     c1.save("chain1.txt").expect("failed to save chain 1");
     c2.save("chain2.txt").expect("failed to save chain 2");
     c3.save("chain3.txt").expect("failed to save chain 3");
@@ -73,4 +207,18 @@ fn main() {
         decomp.0.save(&format!("decomp_r{}.txt", i+1)).expect("failed to save chain.");
         decomp.1.save(&format!("decomp_s{}.txt", i+1)).expect("failed to save chain.");
     }
+
+    let mut chains = vec![("chain1".to_string(), &*c1), ("chain2".to_string(), &*c2), ("chain3".to_string(), &*c3)];
+    chains.extend(result.chains());
+    let chains: Vec<(&str, &Chain)> = chains.iter().map(|(name, c)| (name.as_str(), *c)).collect();
+
+    let properties: Vec<PlyProperty> = chains.iter()
+        .map(|(name, c)| PlyProperty::from_chain(name, c))
+        .collect::<Result<_, _>>()
+        .expect("failed to store chains as PLY properties.");
+    mesh.save_ply("result.ply", PlyFormat::BinaryLittleEndian, &properties).expect("failed to save PLY file.");
+    mesh.save_obj("result.obj", &chains).expect("failed to save OBJ file.");
+    mesh.save_vtu("result.vtu", &chains).expect("failed to save VTU file.");
+    #[cfg(feature = "serde")]
+    std::fs::write("result.json", result.to_json().expect("failed to encode result.")).expect("failed to save JSON file.");
 }
@@ -1,10 +1,19 @@
-use super::{Edge, Mesh};
+use super::{Mesh, Orientation, Tetrahedron};
+use super::validate::Components;
 use std::path::Path;
 use std::rc::Rc;
 use std::fs::File;
 use std::fmt;
+use std::collections::HashMap;
 use std::io::{prelude::*, BufReader};
+use std::iter::Sum;
+use std::ops::{Add, Mul, Neg, Sub};
 
+/// First token of a chain file written by `Chain::save`, followed by the format version.
+const FORMAT_MAGIC: &str = "msp-chain";
+const FORMAT_VERSION: &str = "1";
+
+#[derive(Clone)]
 pub struct Chain {
     pub dim: u32,
     pub mesh: Rc<Mesh>,
@@ -12,10 +21,14 @@ pub struct Chain {
 }
 
 impl Chain {
+    /// The chain with all coefficients zero. 0-chains have a coefficient per vertex, such as the
+    /// endpoints of a path or a set of points.
     pub fn zero(dim: u32, mesh: Rc<Mesh>) -> Chain {
         let coeff = match dim {
+            0 => vec![0.0; mesh.vertices.len()],
             1 => vec![0.0; mesh.edges.len()],
             2 => vec![0.0; mesh.triangles.len()],
+            3 => vec![0.0; mesh.tetrahedra.len()],
             _ => panic!("Unsupported dimension.")
         };
         Chain {
@@ -25,65 +38,601 @@ impl Chain {
         }
     }
 
+    /// Returns `sum(a * chain)` over the given terms. All the chains must be defined on the same
+    /// mesh and have the same dimension.
+    pub fn linear_combination(terms: &[(f64, &Chain)]) -> Result<Chain, String> {
+        let (_, first) = terms.first().ok_or("empty linear combination")?;
+        let mut res = Chain::zero(first.dim, first.mesh.clone());
+        for (a, chain) in terms.iter() {
+            res.check(chain)?;
+            for (x, y) in res.coeff.iter_mut().zip(chain.coeff.iter()) { *x += a * y; }
+        }
+        Ok(res)
+    }
+
+    /// Adds `other` to the chain in place, failing (and leaving the chain untouched) if they are
+    /// defined on different meshes or have different dimensions. There is no `+=` since it could
+    /// not report the error.
+    pub fn try_add_assign(&mut self, other: &Chain) -> Result<(), String> {
+        self.check(other)?;
+        for (x, y) in self.coeff.iter_mut().zip(other.coeff.iter()) { *x += y; }
+        Ok(())
+    }
+
+    /// The boundary of the chain, a chain of one dimension less, with the signs of
+    /// `Mesh::boundary_operator` (the `B` matrix of `median_shape`): the boundary of an edge is
+    /// the vertex it points to minus the one it starts from, as stored in the mesh. A chain is a
+    /// cycle when its boundary is zero.
+    pub fn boundary(&self) -> Result<Chain, String> {
+        if self.dim == 0 { return Err("0-chains have no boundary".to_string()); }
+        Ok(Chain {
+            dim: self.dim - 1,
+            mesh: self.mesh.clone(),
+            coeff: self.mesh.boundary_operator(self.dim).mul_vec(&self.coeff),
+        })
+    }
+
+    /// The vertices of simplex `i` of the chain's dimension.
+    fn simplex_vertices(&self, i: usize) -> Vec<usize> {
+        match self.dim {
+            0 => vec![i],
+            1 => vec![self.mesh.edges[i].0, self.mesh.edges[i].1],
+            2 => vec![self.mesh.triangles[i].0, self.mesh.triangles[i].1, self.mesh.triangles[i].2],
+            3 => vec![self.mesh.tetrahedra[i].0, self.mesh.tetrahedra[i].1, self.mesh.tetrahedra[i].2, self.mesh.tetrahedra[i].3],
+            _ => panic!("unsupported dimension.")
+        }
+    }
+
+    /// Sign turning the coefficient of simplex `i` into that of the simplex oriented as stored in
+    /// the mesh. Edge coefficients are relative to the stored edge, while those of triangles and
+    /// tetrahedra are relative to their sorted vertices, as in `Mesh::boundary_operator`.
+    pub(super) fn stored_sign(&self, i: usize) -> f64 {
+        let orientation = match self.dim {
+            2 => self.mesh.triangles[i].orientation(),
+            3 => self.mesh.tetrahedra[i].orientation(),
+            _ => Orientation::CCW,
+        };
+        if orientation == Orientation::CCW { 1.0 } else { -1.0 }
+    }
+
+    /// Looks up the simplex with the given vertices, returning its index and the sign turning a
+    /// coefficient of the simplex oriented as listed into a coefficient of the chain.
+    fn find_simplex(&self, v: &[usize], tetrahedra: &HashMap<[usize; 4], usize>) -> Option<(usize, f64)> {
+        let (idx, sign) = match v.len() {
+            1 => Some(v[0]).filter(|v| *v < self.mesh.vertices.len()).map(|v| (v, 1.0)),
+            2 => self.mesh.find_edge(v[0], v[1]),
+            3 => self.mesh.find_triangle(v[0], v[1], v[2]),
+            _ => {
+                let mut key = [v[0], v[1], v[2], v[3]];
+                key.sort();
+                let idx = *tetrahedra.get(&key)?;
+                let listed = Tetrahedron(v[0], v[1], v[2], v[3]).orientation();
+                Some((idx, if self.mesh.tetrahedra[idx].orientation() == listed { 1.0 } else { -1.0 }))
+            }
+        }?;
+        Some((idx, sign * self.stored_sign(idx)))
+    }
+
+    /// The geometric mass of the chain: the sum of |coefficient| times the length, area or volume
+    /// of each simplex. Vertices have unit mass.
+    pub fn mass(&self) -> f64 {
+        self.coeff.iter().enumerate()
+            .filter(|(_, c)| **c != 0.0)
+            .map(|(i, c)| c.abs() * match self.dim {
+                0 => 1.0,
+                1 => self.mesh.edges[i].length(&self.mesh),
+                2 => self.mesh.triangles[i].area(&self.mesh),
+                3 => self.mesh.tetrahedra[i].volume(&self.mesh),
+                _ => panic!("unsupported dimension.")
+            })
+            .sum()
+    }
+
+    /// The mass of the boundary of the chain, zero for cycles and 0-chains.
+    pub fn boundary_mass(&self) -> f64 {
+        self.boundary().map(|b| b.mass()).unwrap_or(0.0)
+    }
+
+    /// Sum of the absolute values of the coefficients.
+    pub fn l1_norm(&self) -> f64 {
+        self.coeff.iter().map(|c| c.abs()).sum()
+    }
+
+    /// Euclidean norm of the coefficients.
+    pub fn l2_norm(&self) -> f64 {
+        self.coeff.iter().map(|c| c * c).sum::<f64>().sqrt()
+    }
+
+    /// Largest absolute value of the coefficients.
+    pub fn linf_norm(&self) -> f64 {
+        self.coeff.iter().fold(0.0, |m, c| m.max(c.abs()))
+    }
+
+    /// Number of simplices with a nonzero coefficient.
+    pub fn support_size(&self) -> usize {
+        self.coeff.iter().filter(|c| **c != 0.0).count()
+    }
+
+    /// Number of connected components of the support, where simplices sharing a vertex are
+    /// connected.
+    pub fn num_components(&self) -> usize {
+        let mut components = Components::new(self.mesh.vertices.len());
+        let mut used = Vec::new();
+        for i in (0..self.coeff.len()).filter(|i| self.coeff[*i] != 0.0) {
+            let vertices = self.simplex_vertices(i);
+            for v in vertices.iter().skip(1) { components.union(vertices[0], *v); }
+            used.push(vertices[0]);
+        }
+
+        let mut roots: Vec<usize> = used.into_iter().map(|v| components.find(v)).collect();
+        roots.sort();
+        roots.dedup();
+        roots.len()
+    }
+
+    /// The measurements of the chain in one place, see `ChainSummary`.
+    pub fn summary(&self) -> ChainSummary {
+        ChainSummary {
+            dim: self.dim,
+            support_size: self.support_size(),
+            components: self.num_components(),
+            mass: self.mass(),
+            boundary_mass: self.boundary_mass(),
+            l1_norm: self.l1_norm(),
+            l2_norm: self.l2_norm(),
+            linf_norm: self.linf_norm(),
+        }
+    }
+
+    /// Checks that two chains can be combined: same mesh (by pointer) and same dimension.
+    fn check(&self, other: &Chain) -> Result<(), String> {
+        if !Rc::ptr_eq(&self.mesh, &other.mesh) { return Err("chains are defined on different meshes".to_string()); }
+        if self.dim != other.dim { return Err(format!("cannot combine a {}-chain with a {}-chain", self.dim, other.dim)); }
+        Ok(())
+    }
+
+    /// Loads a chain saved by `save`. Files in the older format, a list of edges or triangles
+    /// (vertex indices only), are still read as 1- or 2-chains with coefficient 1 on every listed
+    /// simplex. Either way simplices may be listed with any orientation: listing one the other way
+    /// round flips the sign of its coefficient. Listing a simplex twice is an error rather than
+    /// adding up or overwriting its coefficient, since `save` never does.
     pub fn load<P: AsRef<Path>>(mesh: Rc<Mesh>, path: P) -> Result<Chain, String> {
         let file = File::open(path).map_err(|e| format!("error opening file: {}", e))?;
-        
-        let mut coeff = vec![0.0; mesh.edges.len()];
 
+        // Non-empty lines with comments removed, as (line number, tokens).
+        let mut lines = Vec::new();
         let reader = BufReader::new(file);
-        for line in reader.lines() {
-            let line = line.unwrap();
-            if line.chars().nth(0) == Some('#') { continue; }
-            let parts: Vec<&str> = line.split_whitespace().collect();
-
-            let i = parts[0].parse::<usize>().map_err(|_| "invalid edge")?;
-            let j = parts[1].parse::<usize>().map_err(|_| "invalid edge")?;
-
-            // Find the edge (i,j) in the mesh
-            let mut found = false;
-            for idx in 0..mesh.edges.len() {
-                if mesh.edges[idx] == Edge(i,j)
-                    || mesh.edges[idx] == Edge(j,i) {
-                    coeff[idx] = 1.0;
-                    found = true;
-                    break;
-                }
+        for (lineno, line) in reader.lines().enumerate() {
+            let line = line.map_err(|e| format!("error reading file: {}", e))?;
+            let text = match line.find('#') {
+                Some(idx) => &line[..idx],
+                None => &line[..],
+            };
+            let parts: Vec<String> = text.split_whitespace().map(|s| s.to_string()).collect();
+            if !parts.is_empty() { lines.push((lineno+1, parts)); }
+        }
+
+        let parse_index = |s: &str, lineno: usize| s.parse::<usize>().map_err(|_| format!("line {}: invalid vertex index '{}'", lineno, s));
+        // Line on which each simplex was listed.
+        let mut listed: HashMap<usize, usize> = HashMap::new();
+        let mut check_repeat = |idx: usize, v: &[usize], lineno: usize| match listed.insert(idx, lineno) {
+            Some(first) => Err(format!("line {}: simplex {:?} is already listed on line {}", lineno, v, first)),
+            None => Ok(()),
+        };
+        let versioned = lines.first().map(|(_, parts)| parts[0] == FORMAT_MAGIC).unwrap_or(false);
+        if !versioned {
+            // Older format: the number of vertices on the first row gives the dimension.
+            let dim = lines.first().map(|(_, parts)| parts.len() as u32 - 1).unwrap_or(1);
+            let mut chain = Chain::zero(dim.clamp(1, 2), mesh.clone());
+            for (lineno, parts) in lines.iter() {
+                if parts.len() != chain.dim as usize + 1 { return Err(format!("line {}: expected {} vertex indices", lineno, chain.dim + 1)); }
+                let v: Vec<usize> = parts.iter().map(|s| parse_index(s, *lineno)).collect::<Result<_, _>>()?;
+                let (idx, sign) = chain.find_simplex(&v, &HashMap::new()).ok_or(format!("line {}: unknown simplex {:?}", lineno, v))?;
+                check_repeat(idx, &v, *lineno)?;
+                chain.coeff[idx] = sign;
             }
+            return Ok(chain);
+        }
 
-            if !found { return Err(format!("unknown edge {:?}", (i,j))) }
+        // Header.
+        let header = |i: usize, key: &str, len: usize| -> Result<&Vec<String>, String> {
+            let (lineno, parts) = lines.get(i).ok_or(format!("missing '{}' header", key))?;
+            if parts[0] != key || parts.len() != len { return Err(format!("line {}: expected '{}' header with {} values", lineno, key, len - 1)); }
+            Ok(parts)
+        };
+        let version = &header(0, FORMAT_MAGIC, 2)?[1];
+        if version != FORMAT_VERSION { return Err(format!("unsupported chain format version {}", version)); }
+        let dim: u32 = header(1, "dim", 2)?[1].parse().ok().filter(|d| *d <= 3).ok_or("line 2: invalid dimension")?;
+
+        let info = header(2, "mesh", 6)?;
+        let counts: Vec<usize> = info[1..5].iter().map(|s| s.parse::<usize>().map_err(|_| format!("invalid simplex count '{}'", s))).collect::<Result<_, _>>()?;
+        if counts != (0..=3).map(|d| mesh.num_simplices(d)).collect::<Vec<usize>>() {
+            return Err(format!("chain was saved for a mesh with {:?} simplices, this one has {:?}", counts, (0..=3).map(|d| mesh.num_simplices(d)).collect::<Vec<usize>>()));
+        }
+        if info[5] != format!("{:016x}", mesh.fingerprint()) {
+            return Err("chain was saved for a different mesh (fingerprints do not match)".to_string());
         }
 
-        Ok(Chain {
-            dim: 1,
-            mesh,
-            coeff,
-        })
+        // Rows: the vertices of a simplex and its coefficient.
+        let tetrahedra: HashMap<[usize; 4], usize> = if dim == 3 {
+            mesh.tetrahedra.iter().enumerate().map(|(i, t)| {
+                let mut key = [t.0, t.1, t.2, t.3];
+                key.sort();
+                (key, i)
+            }).collect()
+        } else { HashMap::new() };
+        let mut chain = Chain::zero(dim, mesh.clone());
+        for (lineno, parts) in lines[3..].iter() {
+            if parts.len() != dim as usize + 2 { return Err(format!("line {}: expected {} vertex indices and a coefficient", lineno, dim + 1)); }
+            let v: Vec<usize> = parts[..=dim as usize].iter().map(|s| parse_index(s, *lineno)).collect::<Result<_, _>>()?;
+            let c: f64 = parts[dim as usize + 1].parse().map_err(|_| format!("line {}: invalid coefficient '{}'", lineno, parts[dim as usize + 1]))?;
+
+            let (idx, sign) = chain.find_simplex(&v, &tetrahedra).ok_or(format!("line {}: unknown simplex {:?}", lineno, v))?;
+            check_repeat(idx, &v, *lineno)?;
+            chain.coeff[idx] = sign * c;
+        }
+
+        Ok(chain)
     }
 
+    /// Saves the chain as text: a header with the format version, the dimension and the size
+    /// and fingerprint of the mesh, then a row per nonzero coefficient with the vertices of the
+    /// simplex (as stored in the mesh) followed by its coefficient with that orientation.
+    /// Coefficients are written in full, so `load` gives back exactly the same chain.
     pub fn save<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
         let mut file = File::create(path)?;
-        for i in 0..self.coeff.len() {
-            if self.coeff[i] != 0.0 {
-                match self.dim {
-                    1 => write!(file, "{} {}\n", self.mesh.edges[i].0, self.mesh.edges[i].1)?,
-                    2 => write!(file, "{} {} {}\n", self.mesh.triangles[i].0, self.mesh.triangles[i].1, self.mesh.triangles[i].2)?,
-                    _ => panic!("unsupported dimension.")
-                }
-            }
+        writeln!(file, "{} {}", FORMAT_MAGIC, FORMAT_VERSION)?;
+        writeln!(file, "dim {}", self.dim)?;
+        let counts: Vec<String> = (0..=3).map(|d| self.mesh.num_simplices(d).to_string()).collect();
+        writeln!(file, "mesh {} {:016x}", counts.join(" "), self.mesh.fingerprint())?;
+
+        for (i, c) in self.coeff.iter().enumerate() {
+            if *c == 0.0 { continue; }
+            let vertices: Vec<String> = self.simplex_vertices(i).iter().map(|v| v.to_string()).collect();
+            writeln!(file, "{} {:?}", vertices.join(" "), c * self.stored_sign(i))?;
         }
         Ok(())
     }
 }
 
+/// Size and shape of a chain at a glance, for logging. See `Chain::summary`.
+#[derive(Debug, Clone)]
+pub struct ChainSummary {
+    pub dim: u32,
+    pub support_size: usize,
+    pub components: usize,
+    pub mass: f64,
+    pub boundary_mass: f64,
+    pub l1_norm: f64,
+    pub l2_norm: f64,
+    pub linf_norm: f64,
+}
+
+impl fmt::Display for ChainSummary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let simplices = ["vertices", "edges", "triangles", "tetrahedra"];
+        write!(f, "{}-chain on {} {} in {} component{}, mass {}, boundary mass {}, norms L1 {} L2 {} Linf {}",
+            self.dim, self.support_size, simplices[self.dim as usize], self.components, if self.components == 1 { "" } else { "s" },
+            self.mass, self.boundary_mass, self.l1_norm, self.l2_norm, self.linf_norm)
+    }
+}
+
+impl Add<&Chain> for &Chain {
+    type Output = Result<Chain, String>;
+
+    fn add(self, rhs: &Chain) -> Self::Output {
+        Chain::linear_combination(&[(1.0, self), (1.0, rhs)])
+    }
+}
+
+impl Add for Chain {
+    type Output = Result<Chain, String>;
+
+    fn add(self, rhs: Chain) -> Self::Output {
+        &self + &rhs
+    }
+}
+
+impl Sub<&Chain> for &Chain {
+    type Output = Result<Chain, String>;
+
+    fn sub(self, rhs: &Chain) -> Self::Output {
+        Chain::linear_combination(&[(1.0, self), (-1.0, rhs)])
+    }
+}
+
+impl Sub for Chain {
+    type Output = Result<Chain, String>;
+
+    fn sub(self, rhs: Chain) -> Self::Output {
+        &self - &rhs
+    }
+}
+
+impl Neg for &Chain {
+    type Output = Chain;
+
+    fn neg(self) -> Chain {
+        self * -1.0
+    }
+}
+
+impl Neg for Chain {
+    type Output = Chain;
+
+    fn neg(self) -> Chain {
+        self * -1.0
+    }
+}
+
+impl Mul<f64> for &Chain {
+    type Output = Chain;
+
+    fn mul(self, rhs: f64) -> Chain {
+        self.clone() * rhs
+    }
+}
+
+impl Mul<f64> for Chain {
+    type Output = Chain;
+
+    fn mul(mut self, rhs: f64) -> Chain {
+        for x in self.coeff.iter_mut() { *x *= rhs; }
+        self
+    }
+}
+
+/// Sums chains, failing if there are none (the mesh and dimension of the result would be
+/// unknown) or if they cannot be combined.
+impl<'a> Sum<&'a Chain> for Result<Chain, String> {
+    fn sum<I: Iterator<Item = &'a Chain>>(mut iter: I) -> Self {
+        let mut res = iter.next().ok_or("empty sum of chains")?.clone();
+        for chain in iter { res.try_add_assign(chain)?; }
+        Ok(res)
+    }
+}
+
+impl Sum<Chain> for Result<Chain, String> {
+    fn sum<I: Iterator<Item = Chain>>(mut iter: I) -> Self {
+        let mut res = iter.next().ok_or("empty sum of chains")?;
+        for chain in iter { res.try_add_assign(&chain)?; }
+        Ok(res)
+    }
+}
+
 impl fmt::Display for Chain {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        for i in 0..self.mesh.edges.len() {
+        // Simplices are written as stored in the mesh, with their coefficient for that orientation.
+        for i in 0..self.coeff.len() {
             if self.coeff[i] == 0.0 { continue; }
-            if self.coeff[i] < 0.0 { write!(f, "- ")?; }
-            if self.coeff[i] > 0.0 { write!(f, "+ ")?; }
-            if self.coeff[i].abs() != 1.0 { write!(f, "{}*{:?} ", self.coeff[i].abs(), self.mesh.edges[i])?; }
-            else { write!(f, "{:?} ", self.mesh.edges[i])?; }
+            let c = self.coeff[i] * self.stored_sign(i);
+            if c < 0.0 { write!(f, "- ")?; }
+            if c > 0.0 { write!(f, "+ ")?; }
+            if c.abs() != 1.0 { write!(f, "{}*", c.abs())?; }
+            match self.dim {
+                0 => write!(f, "{} ", i)?,
+                1 => write!(f, "{:?} ", self.mesh.edges[i])?,
+                2 => write!(f, "{:?} ", self.mesh.triangles[i])?,
+                3 => write!(f, "{:?} ", self.mesh.tetrahedra[i])?,
+                _ => panic!("unsupported dimension.")
+            }
         }
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prelude::generate::{grid, Diagonal};
+    use crate::prelude::{Current, Edge, Tetrahedron, Triangle};
+    use nalgebra::Vector3;
+
+    #[test]
+    fn boundary_follows_stored_edges() {
+        let vertices = vec![Vector3::zeros(), Vector3::x(), Vector3::z()];
+        let edges = vec![Edge(1, 0), Edge(1, 2), Edge(2, 0)];
+        let mesh = Rc::new(Mesh::from_parts(vertices, edges, vec![Triangle(0, 1, 2)], Vec::new()).unwrap());
+
+        let mut chain = Chain::zero(1, mesh.clone());
+        chain.coeff[0] = 1.0;
+        assert_eq!(chain.boundary().unwrap().coeff, vec![1.0, -1.0, 0.0]);
+
+        // The boundary of the triangle is a cycle: 0 -> 1 -> 2 -> 0 on the sorted triangle.
+        let mut triangle = Chain::zero(2, mesh.clone());
+        triangle.coeff[0] = 1.0;
+        let boundary = triangle.boundary().unwrap();
+        assert_eq!(boundary.coeff, vec![-1.0, 1.0, 1.0]);
+        assert_eq!(boundary.boundary_mass(), 0.0);
+    }
+
+    fn sample_chain(mesh: &Rc<Mesh>, dim: u32, offset: usize) -> Chain {
+        let mut chain = Chain::zero(dim, mesh.clone());
+        for (i, c) in chain.coeff.iter_mut().enumerate() { *c = ((i + offset) % 4) as f64 - 1.5; }
+        chain
+    }
+
+    #[test]
+    fn measures() {
+        let mesh = Rc::new(grid(3.0, 3.0, 3, 3, Diagonal::Forward));
+        let path = |p: Vec<usize>| Current { mesh: mesh.clone(), path: p }.as_chain().unwrap();
+
+        // A path of three unit edges with weight 2 and a disjoint edge with weight -1/2.
+        let chain = Chain::linear_combination(&[(2.0, &path(vec![0, 1, 2, 3])), (-0.5, &path(vec![12, 13]))]).unwrap();
+        assert!((chain.mass() - 6.5).abs() < 1e-12);
+        assert_eq!(chain.boundary_mass(), 5.0);
+        assert_eq!(chain.l1_norm(), 6.5);
+        assert_eq!(chain.l2_norm(), 12.25_f64.sqrt());
+        assert_eq!(chain.linf_norm(), 2.0);
+        assert_eq!(chain.support_size(), 4);
+        assert_eq!(chain.num_components(), 2);
+
+        let summary = chain.summary();
+        assert_eq!((summary.dim, summary.support_size, summary.components), (1, 4, 2));
+        assert_eq!((summary.mass, summary.boundary_mass, summary.linf_norm), (chain.mass(), 5.0, 2.0));
+        assert!(summary.to_string().starts_with("1-chain on 4 edges in 2 components, mass "));
+
+        // A closed loop around a cell.
+        let loop_chain = path(vec![5, 6, 10, 9, 5]);
+        assert!((loop_chain.mass() - 4.0).abs() < 1e-12);
+        assert_eq!(loop_chain.boundary_mass(), 0.0);
+        assert_eq!(loop_chain.num_components(), 1);
+
+        // The whole grid, as a 2-chain oriented like its triangles.
+        let mut surface = Chain::zero(2, mesh.clone());
+        for i in 0..surface.coeff.len() { surface.coeff[i] = surface.stored_sign(i); }
+        assert!((surface.mass() - 9.0).abs() < 1e-12);
+        assert!((surface.boundary_mass() - 12.0).abs() < 1e-12);
+        assert_eq!(Chain::zero(1, mesh.clone()).num_components(), 0);
+    }
+
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("msp-chain-{}-{}", std::process::id(), name))
+    }
+
+    /// Loads a chain from a file with the given contents.
+    fn load_str(mesh: &Rc<Mesh>, name: &str, contents: &str) -> Result<Chain, String> {
+        let path = temp_path(name);
+        std::fs::write(&path, contents).unwrap();
+        let chain = Chain::load(mesh.clone(), &path);
+        std::fs::remove_file(&path).unwrap();
+        chain
+    }
+
+    /// Two tetrahedra sharing a face.
+    fn volume_mesh() -> Mesh {
+        let vertices = vec![Vector3::zeros(), Vector3::x(), Vector3::y(), Vector3::z(), Vector3::new(1.0, 1.0, 1.0)];
+        Mesh::from_tetrahedra(vertices, vec![Tetrahedron(0, 1, 2, 3), Tetrahedron(1, 2, 3, 4)])
+    }
+
+    #[test]
+    fn save_load_round_trip() {
+        let mut surface = grid(2.0, 2.0, 2, 2, Diagonal::Alternating);
+        surface.orient2d().unwrap();
+        for mesh in [Rc::new(surface), Rc::new(volume_mesh())] {
+            for dim in 1..=3 {
+                if mesh.num_simplices(dim) == 0 { continue; }
+                let mut chain = Chain::zero(dim, mesh.clone());
+                for (i, c) in chain.coeff.iter_mut().enumerate() { *c = [1.0 / 3.0, -0.1, 0.0, -2.5][i % 4]; }
+
+                let path = temp_path(&format!("round-trip-{}.txt", dim));
+                chain.save(&path).unwrap();
+                let loaded = Chain::load(mesh.clone(), &path);
+                std::fs::remove_file(&path).unwrap();
+                let loaded = loaded.unwrap();
+                assert_eq!(loaded.dim, dim);
+                assert_eq!(loaded.coeff, chain.coeff);
+            }
+        }
+    }
+
+    #[test]
+    fn load_old_format() {
+        let mesh = Rc::new(grid(2.0, 2.0, 2, 2, Diagonal::Forward));
+        let chain = load_str(&mesh, "old.txt", "# a path\n0 1\n2 1\n").unwrap();
+        assert_eq!(chain.dim, 1);
+        let (e01, s01) = mesh.find_edge(0, 1).unwrap();
+        let (e21, s21) = mesh.find_edge(2, 1).unwrap();
+        assert_eq!((chain.coeff[e01], chain.coeff[e21]), (s01, s21));
+        assert_eq!(chain.support_size(), 2);
+    }
+
+    #[test]
+    fn load_old_format_triangles() {
+        let mut mesh = grid(2.0, 2.0, 2, 2, Diagonal::Alternating);
+        mesh.orient2d().unwrap();
+        let mesh = Rc::new(mesh);
+        let (t0, t1) = (mesh.triangles[0], mesh.triangles[1]);
+        let contents = format!("{} {} {}\n{} {} {}\n", t0.0, t0.1, t0.2, t1.1, t1.0, t1.2);
+        let chain = load_str(&mesh, "old-triangles.txt", &contents).unwrap();
+
+        // Relative to the stored triangles: the first as listed, the second reversed.
+        assert_eq!(chain.dim, 2);
+        assert_eq!(chain.coeff[0] * chain.stored_sign(0), 1.0);
+        assert_eq!(chain.coeff[1] * chain.stored_sign(1), -1.0);
+        assert_eq!(chain.support_size(), 2);
+    }
+
+    #[test]
+    fn display_lists_simplices_of_the_chain() {
+        let mesh = Rc::new(grid(1.0, 1.0, 1, 1, Diagonal::Forward));
+        let mut chain = Chain::zero(2, mesh.clone());
+        chain.coeff[0] = 2.0 * chain.stored_sign(0);
+        chain.coeff[1] = -chain.stored_sign(1);
+        assert_eq!(chain.to_string(), format!("+ 2*{:?} - {:?} ", mesh.triangles[0], mesh.triangles[1]));
+        assert!(!chain.to_string().contains("Edge"));
+    }
+
+    #[test]
+    fn load_rejects_other_meshes_and_repeats() {
+        let mesh = Rc::new(grid(2.0, 2.0, 2, 2, Diagonal::Forward));
+        let mut chain = Chain::zero(1, mesh.clone());
+        chain.coeff[0] = 0.5;
+        let path = temp_path("saved.txt");
+        chain.save(&path).unwrap();
+        let saved = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        // Different counts.
+        let bigger = Rc::new(grid(2.0, 2.0, 3, 2, Diagonal::Forward));
+        assert!(load_str(&bigger, "counts.txt", &saved).err().unwrap().contains("simplices"));
+
+        // Same counts, different vertex positions.
+        let stretched = Rc::new(grid(4.0, 2.0, 2, 2, Diagonal::Forward));
+        assert!(load_str(&stretched, "fingerprint.txt", &saved).err().unwrap().contains("fingerprints"));
+
+        let Edge(a, b) = mesh.edges[0];
+        let repeated = format!("{}{} {} 1.0\n", saved, b, a);
+        assert_eq!(load_str(&mesh, "repeated.txt", &repeated).err().unwrap(), format!("line 5: simplex [{}, {}] is already listed on line 4", b, a));
+        let repeated = format!("{} {}\n{} {}\n", a, b, a, b);
+        assert!(load_str(&mesh, "repeated-old.txt", &repeated).err().unwrap().starts_with("line 2:"));
+    }
+
+    #[test]
+    fn operators() {
+        let mesh = Rc::new(grid(2.0, 2.0, 2, 2, Diagonal::Forward));
+        let (a, b) = (sample_chain(&mesh, 1, 0), sample_chain(&mesh, 1, 1));
+        let each = |f: &dyn Fn(f64, f64) -> f64| a.coeff.iter().zip(b.coeff.iter()).map(|(x, y)| f(*x, *y)).collect::<Vec<f64>>();
+
+        assert_eq!((&a + &b).unwrap().coeff, each(&|x, y| x + y));
+        assert_eq!((a.clone() + b.clone()).unwrap().coeff, each(&|x, y| x + y));
+        assert_eq!((&a - &b).unwrap().coeff, each(&|x, y| x - y));
+        assert_eq!((a.clone() - b.clone()).unwrap().coeff, each(&|x, y| x - y));
+        assert_eq!((-&a).coeff, each(&|x, _| -x));
+        assert_eq!((&a * 2.5).coeff, each(&|x, _| 2.5 * x));
+        assert_eq!(Chain::linear_combination(&[(2.0, &a), (-0.5, &b)]).unwrap().coeff, each(&|x, y| 2.0 * x - 0.5 * y));
+
+        let mut c = a.clone();
+        c.try_add_assign(&b).unwrap();
+        assert_eq!(c.coeff, each(&|x, y| x + y));
+
+        let chains = vec![a.clone(), b.clone(), a.clone()];
+        let sum: Result<Chain, String> = chains.iter().sum();
+        assert_eq!(sum.unwrap().coeff, each(&|x, y| 2.0 * x + y));
+        let sum: Result<Chain, String> = chains.into_iter().sum();
+        assert_eq!(sum.unwrap().coeff, each(&|x, y| 2.0 * x + y));
+        let sum: Result<Chain, String> = Vec::<Chain>::new().into_iter().sum();
+        assert!(sum.is_err());
+    }
+
+    #[test]
+    fn operators_check_mesh_and_dimension() {
+        let mesh = Rc::new(grid(2.0, 2.0, 2, 2, Diagonal::Forward));
+        let copy = Rc::new(grid(2.0, 2.0, 2, 2, Diagonal::Forward));
+        let a = sample_chain(&mesh, 1, 0);
+        let other_mesh = sample_chain(&copy, 1, 0);
+        let other_dim = sample_chain(&mesh, 2, 0);
+
+        for other in [&other_mesh, &other_dim] {
+            assert!((&a + other).is_err());
+            assert!((&a - other).is_err());
+            assert!(Chain::linear_combination(&[(1.0, &a), (1.0, other)]).is_err());
+            let sum: Result<Chain, String> = [&a, other].into_iter().sum();
+            assert!(sum.is_err());
+
+            let mut c = a.clone();
+            assert!(c.try_add_assign(other).is_err());
+            assert_eq!(c.coeff, a.coeff);
+        }
+    }
+}
@@ -1,5 +1,6 @@
-use super::{Edge, Mesh, Chain};
+use super::{Mesh, Chain, Wavefront};
 use nalgebra::Vector3;
+use std::collections::HashMap;
 use std::path::Path;
 use std::rc::Rc;
 use std::fs::File;
@@ -18,39 +19,71 @@ impl Current {
         }
     }
 
+    /// Loads a current from a list of points, one per line, snapping each point onto its closest
+    /// vertex however far it is (see `load_within` to reject points off the surface). Wavefront
+    /// OBJ files (`.obj`) are read instead through `Wavefront::currents` and must hold a single
+    /// line object.
     pub fn load<P: AsRef<Path>>(mesh: Rc<Mesh>, path: P) -> Result<Current, String> {
+        let is_obj = path.as_ref().extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.eq_ignore_ascii_case("obj"))
+            .unwrap_or(false);
+        if is_obj {
+            let mut currents = Wavefront::load(path)?.currents(mesh)?;
+            if currents.len() != 1 { return Err(format!("expected a single line object, found {}", currents.len())); }
+            return Ok(currents.remove(0).1);
+        }
+
+        Current::load_within(mesh, path, f64::INFINITY)
+    }
+
+    /// Like `load`, but fails if a point is farther than `max_distance` from the surface, e.g.
+    /// `Mesh::snap_distance`.
+    pub fn load_within<P: AsRef<Path>>(mesh: Rc<Mesh>, path: P, max_distance: f64) -> Result<Current, String> {
         let file = File::open(path).map_err(|e| format!("error opening file: {}", e))?;
         
-        let mut path = Vec::new();
+        let mut points = Vec::new();
 
         let reader = BufReader::new(file);
-        for line in reader.lines() {
-            let line = line.unwrap();
-            if line.chars().nth(0) == Some('#') { continue; }
+        for (num, line) in reader.lines().enumerate() {
+            let line = line.map_err(|e| format!("error reading file: {}", e))?;
             let parts: Vec<&str> = line.split_whitespace().collect();
+            if parts.is_empty() || parts[0].starts_with('#') { continue; }
+            if parts.len() < 3 { return Err(format!("line {}: expected 3 coordinates, found {}", num+1, parts.len())); }
+
+            let x = parts[0].parse::<f64>().map_err(|_| format!("line {}: invalid 'x' coordinate", num+1))?;
+            let y = parts[1].parse::<f64>().map_err(|_| format!("line {}: invalid 'y' coordinate", num+1))?;
+            let z = parts[2].parse::<f64>().map_err(|_| format!("line {}: invalid 'z' coordinate", num+1))?;
+            points.push(Vector3::new(x, y, z));
+        }
+
+        Current::from_points_within(mesh, &points, max_distance)
+    }
+
+    /// Builds a current by snapping each point onto its closest mesh vertex, however far it is.
+    /// Consecutive points that snap to the same vertex are merged.
+    pub fn from_points(mesh: Rc<Mesh>, points: &[Vector3<f64>]) -> Result<Current, String> {
+        Current::from_points_within(mesh, points, f64::INFINITY)
+    }
 
-            let x = parts[0].parse::<f64>().map_err(|_| "invalid 'x' coordinate".to_string())?;
-            let y = parts[1].parse::<f64>().map_err(|_| "invalid 'y' coordinate".to_string())?;
-            let z = parts[2].parse::<f64>().map_err(|_| "invalid 'z' coordinate".to_string())?;
-            let pt = Vector3::new(x, y, z);
-
-            // Find the closest vertex in the mesh.
-            let mut closest = None;
-            let mut min_dist = 0.0;
-            for idx in 0..mesh.vertices.len() {
-                let delta = pt - mesh.vertices[idx];
-                let d2 = delta.norm_squared();
-                if closest.is_none() || d2 < min_dist {
-                    closest = Some(idx);
-                    min_dist = d2;
-                    if min_dist < 0.0000001 { break; } // We seem to have hit it.
+    /// Like `from_points`, but fails if a point is farther than `max_distance` from the surface,
+    /// e.g. `Mesh::snap_distance`.
+    pub fn from_points_within(mesh: Rc<Mesh>, points: &[Vector3<f64>], max_distance: f64) -> Result<Current, String> {
+        let mut path = Vec::new();
+        for pt in points.iter() {
+            if let Some((q, _)) = mesh.closest_point(pt) {
+                let dist = (pt - q).norm();
+                if dist > max_distance {
+                    return Err(format!("{:?} is {} away from the surface (at most {} allowed)", pt, dist, max_distance));
                 }
             }
 
-            // This should never be the case.
-            if closest.is_none() { return Err(format!("Failed to find vertex close to {:?}", pt));  }
+            let closest = match mesh.closest_vertex(pt) {
+                Some(idx) => idx,
+                None => return Err(format!("Failed to find vertex close to {:?}", pt)),
+            };
 
-            path.push(closest.unwrap());
+            if path.last() != Some(&closest) { path.push(closest); }
         }
 
         Ok(Current {
@@ -66,23 +99,124 @@ impl Current {
             let a = self.path[i-1];
             let b = self.path[i];
 
-            let mut found = false;
-            for idx in 0..self.mesh.edges.len() {
-                if self.mesh.edges[idx] == Edge(a,b) {
-                    chain.coeff[idx] = 1.0;
-                    found = true;
-                    break;
-                }
-                else if self.mesh.edges[idx] == Edge(b,a) {
-                    chain.coeff[idx] = -1.0;
-                    found = true;
-                    break;
-                }
+            let (idx, sign) = self.mesh.find_edge(a, b).ok_or(format!("unknown edge {:?}", (a,b)))?;
+            chain.coeff[idx] = sign;
+        }
+
+        Ok(chain)
+    }
+}
+
+impl Chain {
+    /// Splits a 1-chain into maximal oriented paths, the inverse of `Current::as_chain`. Edges are
+    /// grouped by the magnitude of their coefficient and oriented by its sign, then every group is
+    /// cut into paths that stop wherever the edges branch, end or change direction; what is left
+    /// are closed loops, whose path ends on its first vertex. Returns the paths with their
+    /// coefficient, so that adding up `coeff * current.as_chain()` gives the chain back.
+    pub fn to_paths(&self) -> Result<Vec<(f64, Current)>, String> {
+        if self.dim != 1 { return Err(format!("expected a 1-chain, got a {}-chain", self.dim)); }
+
+        // Groups of oriented edges, as (from, to).
+        let mut groups: Vec<(f64, Vec<(usize, usize)>)> = Vec::new();
+        for (edge, c) in self.mesh.edges.iter().zip(self.coeff.iter()) {
+            if *c == 0.0 { continue; }
+            let oriented = if *c > 0.0 { (edge.0, edge.1) } else { (edge.1, edge.0) };
+            match groups.iter_mut().find(|(m, _)| *m == c.abs()) {
+                Some(group) => group.1.push(oriented),
+                None => groups.push((c.abs(), vec![oriented])),
             }
+        }
+
+        let mut paths = Vec::new();
+        for (magnitude, edges) in groups.iter() {
+            let mut outgoing: HashMap<usize, Vec<usize>> = HashMap::new();
+            let mut incoming: HashMap<usize, usize> = HashMap::new();
+            for (k, (a, b)) in edges.iter().enumerate() {
+                outgoing.entry(*a).or_default().push(k);
+                *incoming.entry(*b).or_default() += 1;
+            }
+            // Paths run through vertices with exactly one edge in and one out.
+            let through = |v: usize| incoming.get(&v) == Some(&1) && outgoing.get(&v).map(|o| o.len()) == Some(1);
 
-            if !found { return Err(format!("unknown edge {:?}", (a,b))); }
+            let mut used = vec![false; edges.len()];
+            let walk = |start: usize, used: &mut Vec<bool>| {
+                let mut path = vec![edges[start].0];
+                let mut k = start;
+                loop {
+                    used[k] = true;
+                    let v = edges[k].1;
+                    path.push(v);
+                    if !through(v) { break; }
+                    k = outgoing[&v][0];
+                    if used[k] { break; }
+                }
+                path
+            };
+
+            // Open paths start at the other vertices, then only closed loops are left.
+            for k in 0..edges.len() {
+                if !used[k] && !through(edges[k].0) { paths.push((*magnitude, walk(k, &mut used))); }
+            }
+            for k in 0..edges.len() {
+                if !used[k] { paths.push((*magnitude, walk(k, &mut used))); }
+            }
         }
 
-        Ok(chain)
+        Ok(paths.into_iter()
+            .map(|(c, path)| (c, Current { mesh: self.mesh.clone(), path }))
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prelude::generate::{grid, Diagonal};
+
+    #[test]
+    fn distance_limit_is_opt_in() {
+        let mesh = Rc::new(grid(2.0, 2.0, 2, 2, Diagonal::Forward));
+        let points = [Vector3::new(-1.0, 0.0, -1.0), Vector3::new(0.0, 5.0, -1.0)];
+
+        let current = Current::from_points(mesh.clone(), &points).unwrap();
+        assert_eq!(current.path, vec![0, 1]);
+        assert!(Current::from_points_within(mesh.clone(), &points, mesh.snap_distance()).is_err());
+    }
+
+    #[test]
+    fn load_skips_blank_lines_and_reports_short_ones() {
+        let mesh = Rc::new(grid(2.0, 2.0, 2, 2, Diagonal::Forward));
+        let path = std::env::temp_dir().join(format!("msp-current-{}-points.txt", std::process::id()));
+
+        std::fs::write(&path, "# path\n-1 0 -1\n\n   \n0 0 -1\n").unwrap();
+        let current = Current::load(mesh.clone(), &path);
+        std::fs::write(&path, "-1 0 -1\n0 0\n").unwrap();
+        let short = Current::load(mesh.clone(), &path);
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(current.unwrap().path, vec![0, 1]);
+        assert_eq!(short.err().unwrap(), "line 2: expected 3 coordinates, found 2");
+    }
+
+    #[test]
+    fn to_paths_reconstructs_the_chain() {
+        let mesh = Rc::new(grid(3.0, 3.0, 3, 3, Diagonal::Forward));
+        let path = |p: Vec<usize>| Current { mesh: mesh.clone(), path: p }.as_chain().unwrap();
+        let chain = Chain::linear_combination(&[
+            (2.0, &path(vec![0, 1, 2, 3])),
+            (2.0, &path(vec![1, 5])),
+            (-1.0, &path(vec![5, 6, 10, 9, 5])),
+            (0.5, &path(vec![15, 14])),
+        ]).unwrap();
+
+        let paths = chain.to_paths().unwrap();
+        let terms: Vec<(f64, Chain)> = paths.iter().map(|(c, p)| (*c, p.as_chain().unwrap())).collect();
+        let terms: Vec<(f64, &Chain)> = terms.iter().map(|(c, p)| (*c, p)).collect();
+        assert_eq!(Chain::linear_combination(&terms).unwrap().coeff, chain.coeff);
+
+        // The loop comes back as a closed path and the branch at vertex 1 splits the first path.
+        assert!(paths.iter().any(|(c, p)| *c == 1.0 && p.path.len() == 5 && p.path.first() == p.path.last()));
+        assert_eq!(paths.iter().filter(|(c, _)| *c == 2.0).count(), 3);
+        assert!(Chain::zero(2, mesh.clone()).to_paths().is_err());
     }
 }
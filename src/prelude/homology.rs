@@ -0,0 +1,253 @@
+//! Simplicial homology of a mesh, by reducing the boundary operators column by column (the
+//! "standard algorithm" of persistent homology).
+
+use super::{Chain, Mesh, SparseMatrix};
+use std::collections::HashMap;
+use std::fmt;
+
+/// Coefficients smaller than this (relative to the largest one) are zero over Q.
+const TOLERANCE: f64 = 1e-9;
+
+/// The coefficients the boundary operators are reduced over: `bool` for Z₂ and `f64` for Q.
+trait Field: Copy {
+    fn from_f64(x: f64) -> Self;
+    fn is_zero(self) -> bool;
+    /// `self - factor * x`.
+    fn sub_mul(self, factor: Self, x: Self) -> Self;
+    fn div(self, other: Self) -> Self;
+}
+
+impl Field for bool {
+    fn from_f64(x: f64) -> Self { x != 0.0 }
+    fn is_zero(self) -> bool { !self }
+    fn sub_mul(self, factor: Self, x: Self) -> Self { self ^ (factor & x) }
+    fn div(self, _other: Self) -> Self { self }
+}
+
+impl Field for f64 {
+    fn from_f64(x: f64) -> Self { x }
+    fn is_zero(self) -> bool { self.abs() < TOLERANCE }
+    fn sub_mul(self, factor: Self, x: Self) -> Self { self - factor * x }
+    fn div(self, other: Self) -> Self { self / other }
+}
+
+/// A sparse column, sorted by row.
+type Column<F> = Vec<(usize, F)>;
+
+/// `a - factor * b`, dropping the entries that cancel out.
+fn sub_mul<F: Field>(a: &Column<F>, factor: F, b: &Column<F>) -> Column<F> {
+    let mut res = Vec::with_capacity(a.len() + b.len());
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() || j < b.len() {
+        let entry = if j == b.len() || (i < a.len() && a[i].0 < b[j].0) { i += 1; a[i-1] }
+            else if i == a.len() || b[j].0 < a[i].0 { j += 1; (b[j-1].0, F::from_f64(0.0).sub_mul(factor, b[j-1].1)) }
+            else { i += 1; j += 1; (a[i-1].0, a[i-1].1.sub_mul(factor, b[j-1].1)) };
+        if !entry.1.is_zero() { res.push(entry); }
+    }
+    res
+}
+
+/// The reduced form R = D V of a matrix D, where every nonzero column of R has a different
+/// lowest nonzero row (its pivot) and V is upper triangular with ones on the diagonal.
+struct Reduction<F> {
+    columns: Vec<Column<F>>,
+    /// The column of R having each pivot row.
+    pivots: HashMap<usize, usize>,
+    /// The columns of V, when tracked.
+    v: Vec<Column<F>>,
+}
+
+impl<F: Field> Reduction<F> {
+    fn new(matrix: &SparseMatrix, track: bool) -> Self {
+        let transpose = matrix.transpose();
+        let mut res = Reduction { columns: Vec::with_capacity(matrix.ncols), pivots: HashMap::new(), v: Vec::new() };
+
+        for j in 0..matrix.ncols {
+            let mut col: Column<F> = transpose.row(j).map(|(i, x)| (i, F::from_f64(x))).collect();
+            let mut v = vec![(j, F::from_f64(1.0))];
+            while let Some(&(low, x)) = col.last() {
+                let Some(&k) = res.pivots.get(&low) else { break };
+                let factor = x.div(res.columns[k].last().unwrap().1);
+                col = sub_mul(&col, factor, &res.columns[k]);
+                if track { v = sub_mul(&v, factor, &res.v[k]); }
+            }
+
+            if let Some(&(low, _)) = col.last() { res.pivots.insert(low, j); }
+            res.columns.push(col);
+            if track { res.v.push(v); }
+        }
+
+        res
+    }
+
+    fn rank(&self) -> usize {
+        self.pivots.len()
+    }
+}
+
+/// Homology of a mesh, see `Mesh::homology`.
+pub struct Homology<'a> {
+    mesh: &'a Mesh,
+    /// Betti numbers over Z₂, from dimension 0 up to the dimension of the mesh.
+    pub betti_z2: Vec<usize>,
+    /// Betti numbers over Q, from dimension 0 up to the dimension of the mesh.
+    pub betti_q: Vec<usize>,
+    /// Coefficients (over the edges) of 1-cycles whose classes form a basis of H₁ over Q.
+    pub cycles: Vec<Vec<f64>>,
+    /// Edge with the highest index of each cycle, where its coefficient is 1.
+    cycle_lows: HashMap<usize, usize>,
+    /// The reduced boundaries of the triangles.
+    boundaries: Option<Reduction<f64>>,
+}
+
+impl fmt::Display for Homology<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "betti numbers over Z2: {:?}", self.betti_z2)?;
+        write!(f, "betti numbers over Q: {:?}", self.betti_q)
+    }
+}
+
+impl<'a> Homology<'a> {
+    /// Coordinates of the homology class of a 1-cycle in the basis given by `cycles`.
+    pub fn class(&self, chain: &Chain) -> Result<Vec<f64>, String> {
+        self.check(chain)?;
+        if !self.is_cycle(&chain.coeff) { return Err("chain is not a cycle".to_string()); }
+        self.reduce(chain.coeff.clone()).ok_or("chain is not a cycle".to_string())
+    }
+
+    /// Whether two 1-chains differ by a boundary, i.e. whether `a - b` is the boundary of a
+    /// 2-chain. Chains with different boundaries are never homologous.
+    pub fn homologous(&self, a: &Chain, b: &Chain) -> Result<bool, String> {
+        self.check(a)?;
+        self.check(b)?;
+        let diff: Vec<f64> = a.coeff.iter().zip(b.coeff.iter()).map(|(x, y)| x - y).collect();
+        if !self.is_cycle(&diff) { return Ok(false); }
+
+        let scale = diff.iter().fold(1.0_f64, |m, x| m.max(x.abs()));
+        Ok(self.reduce(diff).map(|c| c.iter().all(|x| x.abs() < TOLERANCE * scale)).unwrap_or(false))
+    }
+
+    fn check(&self, chain: &Chain) -> Result<(), String> {
+        if !std::ptr::eq(&*chain.mesh, self.mesh) { return Err("chain is defined on another mesh".to_string()); }
+        if chain.dim != 1 { return Err(format!("expected a 1-chain, got a {}-chain", chain.dim)); }
+        Ok(())
+    }
+
+    fn is_cycle(&self, coeff: &[f64]) -> bool {
+        let scale = coeff.iter().fold(1.0_f64, |m, x| m.max(x.abs()));
+        self.mesh.boundary_operator(1).mul_vec(coeff).iter().all(|x| x.abs() < TOLERANCE * scale)
+    }
+
+    /// Writes a cycle as a boundary plus a combination of `cycles`, returning the coefficients of
+    /// the latter, or `None` if it is not a cycle. Every boundary column and every cycle has a
+    /// different highest edge, so the chain is reduced from its highest edge downwards.
+    fn reduce(&self, mut x: Vec<f64>) -> Option<Vec<f64>> {
+        let tol = TOLERANCE * x.iter().fold(1.0_f64, |m, x| m.max(x.abs()));
+        let mut coords = vec![0.0; self.cycles.len()];
+        for j in (0..x.len()).rev() {
+            if x[j].abs() < tol { continue; }
+
+            let pivot = self.boundaries.as_ref().and_then(|b| b.pivots.get(&j).map(|k| &b.columns[*k]));
+            if let Some(col) = pivot {
+                let factor = x[j] / col.last().unwrap().1;
+                for (i, v) in col.iter() { x[*i] -= factor * v; }
+            } else if let Some(&h) = self.cycle_lows.get(&j) {
+                coords[h] = x[j];
+                let factor = x[j];
+                for (i, v) in self.cycles[h].iter().enumerate() { x[i] -= factor * v; }
+            } else {
+                return None;
+            }
+        }
+
+        Some(coords)
+    }
+}
+
+impl Mesh {
+    /// Computes the simplicial homology of the mesh: its Betti numbers over Z₂ and Q, and a basis
+    /// of H₁ over Q made of explicit cycles. The two sets of Betti numbers only differ when the
+    /// homology has torsion, as for closed non-orientable surfaces.
+    pub fn homology(&self) -> Homology<'_> {
+        let top = (0..=3).rev().find(|d| self.num_simplices(*d) > 0).unwrap_or(0);
+        let operators: Vec<SparseMatrix> = (1..=top).map(|d| self.boundary_operator(d)).collect();
+
+        // Ranks of the boundary operators from dimension 0 (always 0) to top+1 (also 0).
+        let betti = |ranks: Vec<usize>| -> Vec<usize> {
+            (0..=top as usize).map(|d| self.num_simplices(d as u32) - ranks[d] - ranks[d+1]).collect()
+        };
+
+        let mut ranks_z2 = vec![0; top as usize + 2];
+        for (d, op) in operators.iter().enumerate() { ranks_z2[d+1] = Reduction::<bool>::new(op, false).rank(); }
+
+        let reductions: Vec<Reduction<f64>> = operators.iter().enumerate()
+            .map(|(d, op)| Reduction::new(op, d == 0))
+            .collect();
+        let mut ranks_q = vec![0; top as usize + 2];
+        for (d, r) in reductions.iter().enumerate() { ranks_q[d+1] = r.rank(); }
+
+        let mut reductions = reductions.into_iter();
+        let edges = reductions.next();
+        let boundaries = reductions.next();
+
+        // The edges closing a cycle that does not bound, with that cycle.
+        let mut cycles = Vec::new();
+        let mut cycle_lows = HashMap::new();
+        if let Some(edges) = edges {
+            for j in 0..self.edges.len() {
+                if !edges.columns[j].is_empty() { continue; }
+                if boundaries.as_ref().map(|b| b.pivots.contains_key(&j)).unwrap_or(false) { continue; }
+
+                let mut coeff = vec![0.0; self.edges.len()];
+                for (i, x) in edges.v[j].iter() { coeff[*i] = *x; }
+                cycle_lows.insert(j, cycles.len());
+                cycles.push(coeff);
+            }
+        }
+
+        Homology {
+            mesh: self,
+            betti_z2: betti(ranks_z2),
+            betti_q: betti(ranks_q),
+            cycles,
+            cycle_lows,
+            boundaries,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::prelude::generate::{grid, icosphere, torus, Diagonal};
+    use crate::prelude::Current;
+    use std::rc::Rc;
+
+    #[test]
+    fn betti_numbers() {
+        let cases = [
+            (torus(2.0, 0.5, 6, 4), vec![1, 2, 1]),
+            (icosphere(1.0, 1), vec![1, 0, 1]),
+            (grid(2.0, 2.0, 3, 3, Diagonal::Alternating), vec![1, 0, 0]),
+        ];
+        for (mesh, betti) in cases.iter() {
+            let h = mesh.homology();
+            assert_eq!(&h.betti_z2, betti);
+            assert_eq!(&h.betti_q, betti);
+        }
+    }
+
+    #[test]
+    fn torus_loops() {
+        let (segments, rings) = (6, 4);
+        let mesh = Rc::new(torus(2.0, 0.5, segments, rings));
+        let meridian = |i: usize| Current { mesh: mesh.clone(), path: (0..=rings).map(|j| i * rings + j % rings).collect() }.as_chain().unwrap();
+        let longitude = Current { mesh: mesh.clone(), path: (0..=segments).map(|i| (i % segments) * rings).collect() }.as_chain().unwrap();
+
+        let h = mesh.homology();
+        assert!(h.homologous(&meridian(0), &meridian(3)).unwrap());
+        assert!(!h.homologous(&meridian(0), &longitude).unwrap());
+        assert!(!h.homologous(&meridian(0), &-meridian(0)).unwrap());
+        assert_eq!(h.class(&meridian(0)).unwrap().iter().filter(|c| c.abs() > 1e-9).count(), 1);
+        assert!(h.class(&Current { mesh: mesh.clone(), path: vec![0, 1] }.as_chain().unwrap()).is_err());
+    }
+}
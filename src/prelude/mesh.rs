@@ -1,8 +1,13 @@
+pub mod generate;
+pub mod spatial;
+
+use super::{AxisPlane, PlyFormat, SparseMatrix, Wavefront};
+use spatial::{Bvh, KdTree};
 use nalgebra::Vector3;
-use std::collections::VecDeque;
-use std::fs::File;
+use std::cell::OnceCell;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
 use std::path::Path;
-use std::io::{prelude::*, BufReader};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Orientation {
@@ -10,17 +15,80 @@ pub enum Orientation {
     CW
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct Edge(pub usize, pub usize);
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Triangle(pub usize, pub usize, pub usize);
 
-/// Represents a mesh in 3D. Assumes that the y-axis is the upwards direction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Tetrahedron(pub usize, pub usize, pub usize, pub usize);
+
+/// Represents a mesh in 3D. `orient2d` and `Triangle::signed_area` assume that the y-axis is the
+/// upwards direction; see `OrientationMode` for other choices.
+///
+/// Surface meshes have no tetrahedra; volume meshes also hold every face of their tetrahedra in
+/// `triangles`.
+///
+/// Edges and triangles are indexed by their (sorted) vertices for `find_edge` and
+/// `find_triangle`, and the incidences between simplices of neighbouring dimensions are
+/// precomputed (`triangle_edges`, `edge_triangles`, `one_ring`, ...). Reorienting simplices in
+/// place keeps both valid; adding, removing or reordering them requires rebuilding the mesh (see
+/// `Mesh::from_parts`). Likewise, the spatial indices used by `closest_vertex` and
+/// `closest_point` are built from the vertex positions on first use.
 pub struct Mesh {
     pub vertices: Vec<Vector3<f64>>,
     pub edges: Vec<Edge>,
-    pub triangles: Vec<Triangle>
+    pub triangles: Vec<Triangle>,
+    pub tetrahedra: Vec<Tetrahedron>,
+    edge_index: HashMap<(usize, usize), usize>,
+    triangle_index: HashMap<[usize; 3], usize>,
+    vertex_edges: Vec<Vec<usize>>,
+    vertex_triangles: Vec<Vec<usize>>,
+    triangle_edges: Vec<[usize; 3]>,
+    edge_triangles: Vec<Vec<usize>>,
+    tetrahedron_triangles: Vec<[usize; 4]>,
+    triangle_tetrahedra: Vec<Vec<usize>>,
+    vertex_tree: OnceCell<KdTree>,
+    triangle_tree: OnceCell<Bvh>,
+}
+
+/// How `Mesh::orient_by` chooses which side of each triangle its normal points to.
+#[derive(Debug, Clone, PartialEq)]
+pub enum OrientationMode {
+    /// Orients each connected component consistently, with normals pointing out of the volume it
+    /// encloses (positive signed volume). Meant for closed surfaces such as spheres or tori.
+    Outward,
+    /// Normals point to the positive side of the plane (see `AxisPlane::normal`). Meant for
+    /// planar meshes.
+    Plane(AxisPlane),
+    /// Normals have a positive component along the given direction.
+    Direction(Vector3<f64>),
+    /// Normals agree with a reference normal field, given per vertex and averaged over each
+    /// triangle.
+    VertexNormals(Vec<Vector3<f64>>),
+}
+
+/// The error returned by `Mesh::orient` and `Mesh::orient_by`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum OrientationError {
+    /// The surface is not orientable. `cycle` lists the indices of a cycle of neighbouring
+    /// triangles (the last one neighbouring the first) along which the orientation gets
+    /// reversed, such as the core of a Möbius strip.
+    NonOrientable { cycle: Vec<usize> },
+    /// `OrientationMode::VertexNormals` was not given one normal per vertex.
+    NormalCount { expected: usize, found: usize },
+}
+
+impl fmt::Display for OrientationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            OrientationError::NonOrientable { cycle } =>
+                write!(f, "mesh is not orientable, the faces {:?} cannot be oriented consistently", cycle),
+            OrientationError::NormalCount { expected, found } =>
+                write!(f, "expected one normal per vertex ({}), got {}", expected, found),
+        }
+    }
 }
 
 impl Edge {
@@ -81,11 +149,36 @@ impl Triangle {
         self.signed_area(mesh).abs()    
     }
 
+    /// The unit normal given by the winding of the triangle (right-hand rule), or zero if the
+    /// triangle is degenerate.
+    pub fn normal(&self, mesh: &Mesh) -> Vector3<f64> {
+        let a = mesh.vertices[self.0];
+        let n = (mesh.vertices[self.1] - a).cross(&(mesh.vertices[self.2] - a));
+        n.try_normalize(0.0).unwrap_or(Vector3::zeros())
+    }
+
     pub fn is_face(&self, edge: &Edge) -> bool {
         let v = [self.0, self.1, self.2];
         v.contains(&edge.0) && v.contains(&edge.1)
     }
 
+    pub fn induce_orientation(&mut self, tet: &Tetrahedron) {
+        let mut o = Vec::new();
+        let mut odd_idx = false;
+        for (i, v) in [tet.0, tet.1, tet.2, tet.3].iter().enumerate() {
+            if self.0 != *v && self.1 != *v && self.2 != *v {
+                odd_idx = i % 2 == 1;
+            } else {
+                o.push(*v);
+            }
+        }
+
+        self.0 = o[0];
+        self.1 = o[1];
+        self.2 = o[2];
+        if odd_idx { self.swap_orientation(); }
+    }
+
     pub fn swap_orientation(&mut self) {
         let tmp = self.0;
         self.0 = self.1;
@@ -113,70 +206,417 @@ impl Triangle {
     }
 }
 
+impl Tetrahedron {
+    /// Volume of the tetrahedron, positive when (0, 1, 2) winds counter-clockwise seen from 3 (the
+    /// faces returned by `faces` then point outwards).
+    pub fn signed_volume(&self, mesh: &Mesh) -> f64 {
+        let a = mesh.vertices[self.0];
+        let ab = mesh.vertices[self.1] - a;
+        let ac = mesh.vertices[self.2] - a;
+        let ad = mesh.vertices[self.3] - a;
+        ab.cross(&ac).dot(&ad) / 6.0
+    }
+
+    pub fn volume(&self, mesh: &Mesh) -> f64 {
+        self.signed_volume(mesh).abs()
+    }
+
+    pub fn is_face(&self, tri: &Triangle) -> bool {
+        let v = [self.0, self.1, self.2, self.3];
+        v.contains(&tri.0) && v.contains(&tri.1) && v.contains(&tri.2)
+    }
+
+    pub fn faces(&self) -> [Triangle; 4] {
+        [
+            Triangle(self.1, self.2, self.3),
+            Triangle(self.0, self.3, self.2),
+            Triangle(self.0, self.1, self.3),
+            Triangle(self.0, self.2, self.1),
+        ]
+    }
+
+    pub fn swap_orientation(&mut self) {
+        std::mem::swap(&mut self.0, &mut self.1);
+    }
+
+    pub fn orientation(&self) -> Orientation {
+        // Parity of the permutation sorting the vertices.
+        let v = [self.0, self.1, self.2, self.3];
+        let mut inversions = 0;
+        for i in 0..4 {
+            for j in i+1..4 {
+                if v[i] > v[j] { inversions += 1; }
+            }
+        }
+
+        if inversions % 2 == 0 { Orientation::CCW } else { Orientation::CW }
+    }
+}
+
+/// The (lexicographically oriented) edges of the triangles, in the order they are first
+/// encountered.
+fn collect_edges(triangles: &[Triangle]) -> Vec<Edge> {
+    let mut seen = HashSet::new();
+    let mut edges = Vec::new();
+    for &Triangle(i, j, k) in triangles.iter() {
+        for (a, b) in [(i, j), (i, k), (j, k)] {
+            let edge = Edge(a.min(b), a.max(b));
+            if seen.insert(edge) { edges.push(edge); }
+        }
+    }
+
+    edges
+}
+
 impl Mesh {
-    pub fn load<P: AsRef<Path>>(path: P) -> Result<Mesh, String> {
-        let file = File::open(path).map_err(|e| format!("error opening file: {}", e))?;
-        
-        let mut num_vertices = 0;
-        let mut vertices = Vec::new();
-        let mut edges = Vec::new();
-        let mut triangles = Vec::new();
+    /// Builds a mesh from its vertices and triangles, collecting the (lexicographically oriented)
+    /// edges in the order they are first encountered.
+    pub fn new(vertices: Vec<Vector3<f64>>, triangles: Vec<Triangle>) -> Mesh {
+        let edges = collect_edges(&triangles);
+        Mesh::from_parts(vertices, edges, triangles, Vec::new()).expect("triangle refers to a missing vertex.")
+    }
+
+    /// Builds a mesh from explicit simplices, keeping their order and orientation. Fails if a
+    /// simplex refers to a missing vertex, or if the edges and triangles do not contain every
+    /// face of the triangles and tetrahedra.
+    pub fn from_parts(vertices: Vec<Vector3<f64>>, edges: Vec<Edge>, triangles: Vec<Triangle>, tetrahedra: Vec<Tetrahedron>) -> Result<Mesh, String> {
+        let n = vertices.len();
+        if let Some(e) = edges.iter().find(|e| e.0 >= n || e.1 >= n) { return Err(format!("{:?} refers to a missing vertex", e)); }
+        if let Some(t) = triangles.iter().find(|t| t.0 >= n || t.1 >= n || t.2 >= n) { return Err(format!("{:?} refers to a missing vertex", t)); }
+        if let Some(t) = tetrahedra.iter().find(|t| t.0 >= n || t.1 >= n || t.2 >= n || t.3 >= n) { return Err(format!("{:?} refers to a missing vertex", t)); }
+
+        let mut edge_index = HashMap::with_capacity(edges.len());
+        let mut vertex_edges = vec![Vec::new(); n];
+        for (idx, e) in edges.iter().enumerate() {
+            edge_index.entry((e.0.min(e.1), e.0.max(e.1))).or_insert(idx);
+            vertex_edges[e.0].push(idx);
+            if e.1 != e.0 { vertex_edges[e.1].push(idx); }
+        }
 
-        let mut lineno = 0;
-        let reader = BufReader::new(file);
-        for line in reader.lines() {
-            let line = line.unwrap();
-            if line.chars().nth(0) == Some('#') { continue; }
-            let parts: Vec<&str> = line.split_whitespace().collect();
-
-            lineno += 1;
-            if lineno == 1 {
-                if parts[0] != "OFF" { return Err("not an OFF file: missing header".to_string()); }
-            } 
-            else if lineno == 2 {
-                num_vertices = parts[0].parse::<usize>().map_err(|_| "invalid number of vertices provided".to_string())?;
+        let mut triangle_index = HashMap::with_capacity(triangles.len());
+        let mut vertex_triangles = vec![Vec::new(); n];
+        let mut triangle_edges = Vec::with_capacity(triangles.len());
+        let mut edge_triangles = vec![Vec::new(); edges.len()];
+        for (idx, t) in triangles.iter().enumerate() {
+            let mut key = [t.0, t.1, t.2];
+            key.sort();
+            triangle_index.entry(key).or_insert(idx);
+            for v in [t.0, t.1, t.2] { vertex_triangles[v].push(idx); }
+
+            let mut sides = [0; 3];
+            for (k, (a, b)) in [(t.0, t.1), (t.0, t.2), (t.1, t.2)].into_iter().enumerate() {
+                sides[k] = *edge_index.get(&(a.min(b), a.max(b))).ok_or(format!("{:?} has no edge {:?}", t, (a, b)))?;
+                edge_triangles[sides[k]].push(idx);
             }
-            else if lineno <= num_vertices + 2 {
-                let x = parts[0].parse::<f64>().map_err(|_| "invalid 'x' coordinate".to_string())?;
-                let y = parts[1].parse::<f64>().map_err(|_| "invalid 'y' coordinate".to_string())?;
-                let z = parts[2].parse::<f64>().map_err(|_| "invalid 'z' coordinate".to_string())?;
-                vertices.push(Vector3::new(x, y, z));
-            } 
-            else {
-                let c = parts[0].parse::<usize>().map_err(|_| "invalid face".to_string())?;
-                if c != 3 { return Err("invalid face: not a triangle".to_string()) }
-
-                let i = parts[1].parse::<usize>().map_err(|_| "invalid face".to_string())?;
-                let j = parts[2].parse::<usize>().map_err(|_| "invalid face".to_string())?;
-                let k = parts[3].parse::<usize>().map_err(|_| "invalid face".to_string())?;
-
-                if i >= vertices.len() || j >= vertices.len() || k >= vertices.len() { return Err("invalid face: index out of bounds.".to_string()); }
-                triangles.push(Triangle(i, j, k));
-                if !edges.contains(&Edge(i,j)) && !edges.contains(&Edge(j,i)) { edges.push(Edge(i.min(j),j.max(i))); }
-                if !edges.contains(&Edge(i,k)) && !edges.contains(&Edge(k,i)) { edges.push(Edge(i.min(k),k.max(i))); }
-                if !edges.contains(&Edge(j,k)) && !edges.contains(&Edge(k,j)) { edges.push(Edge(j.min(k),k.max(j))); }
+            triangle_edges.push(sides);
+        }
+
+        let mut tetrahedron_triangles = Vec::with_capacity(tetrahedra.len());
+        let mut triangle_tetrahedra = vec![Vec::new(); triangles.len()];
+        for (idx, t) in tetrahedra.iter().enumerate() {
+            let mut faces = [0; 4];
+            for (k, f) in t.faces().into_iter().enumerate() {
+                let mut key = [f.0, f.1, f.2];
+                key.sort();
+                faces[k] = *triangle_index.get(&key).ok_or(format!("{:?} has no face {:?}", t, f))?;
+                triangle_tetrahedra[faces[k]].push(idx);
             }
+            tetrahedron_triangles.push(faces);
         }
 
         Ok(Mesh {
             vertices,
             edges,
             triangles,
+            tetrahedra,
+            edge_index,
+            triangle_index,
+            vertex_edges,
+            vertex_triangles,
+            triangle_edges,
+            edge_triangles,
+            tetrahedron_triangles,
+            triangle_tetrahedra,
+            vertex_tree: OnceCell::new(),
+            triangle_tree: OnceCell::new(),
         })
     }
+
+    /// Builds a volume mesh from its vertices and tetrahedra. The triangles are the faces of the
+    /// tetrahedra, oriented as induced by the first tetrahedron they belong to.
+    pub fn from_tetrahedra(vertices: Vec<Vector3<f64>>, tetrahedra: Vec<Tetrahedron>) -> Mesh {
+        let mut seen = HashSet::new();
+        let mut triangles = Vec::new();
+        for tet in tetrahedra.iter() {
+            for tri in tet.faces() {
+                let mut key = [tri.0, tri.1, tri.2];
+                key.sort();
+                if seen.insert(key) { triangles.push(tri); }
+            }
+        }
+
+        let edges = collect_edges(&triangles);
+        Mesh::from_parts(vertices, edges, triangles, tetrahedra).expect("tetrahedron refers to a missing vertex.")
+    }
+
+    /// Looks up the edge between `a` and `b`, returning its index and `1.0` if it is stored as
+    /// `Edge(a, b)` or `-1.0` if it is stored as `Edge(b, a)`.
+    pub fn find_edge(&self, a: usize, b: usize) -> Option<(usize, f64)> {
+        let idx = *self.edge_index.get(&(a.min(b), a.max(b)))?;
+        let sign = if self.edges[idx] == Edge(a, b) { 1.0 } else { -1.0 };
+        Some((idx, sign))
+    }
+
+    /// Looks up the triangle with vertices `a`, `b` and `c`, returning its index and `1.0` if
+    /// (a, b, c) has the orientation of the stored triangle or `-1.0` otherwise.
+    pub fn find_triangle(&self, a: usize, b: usize, c: usize) -> Option<(usize, f64)> {
+        let mut key = [a, b, c];
+        key.sort();
+        let idx = *self.triangle_index.get(&key)?;
+        let sign = if self.triangles[idx].orientation() == Triangle(a, b, c).orientation() { 1.0 } else { -1.0 };
+        Some((idx, sign))
+    }
+
+    /// Indices of the edges incident to vertex `v`.
+    pub fn vertex_edges(&self, v: usize) -> &[usize] {
+        &self.vertex_edges[v]
+    }
+
+    /// Indices of the triangles incident to vertex `v`.
+    pub fn vertex_triangles(&self, v: usize) -> &[usize] {
+        &self.vertex_triangles[v]
+    }
+
+    /// The vertices sharing an edge with vertex `v`.
+    pub fn one_ring(&self, v: usize) -> Vec<usize> {
+        self.vertex_edges[v].iter()
+            .map(|e| if self.edges[*e].0 == v { self.edges[*e].1 } else { self.edges[*e].0 })
+            .collect()
+    }
+
+    /// Indices of the three sides of triangle `tri`: (0, 1), (0, 2) and (1, 2).
+    pub fn triangle_edges(&self, tri: usize) -> [usize; 3] {
+        self.triangle_edges[tri]
+    }
+
+    /// Indices of the triangles having edge `edge` as a side.
+    pub fn edge_triangles(&self, edge: usize) -> &[usize] {
+        &self.edge_triangles[edge]
+    }
+
+    /// Indices of the triangles sharing a side with triangle `tri`.
+    pub fn triangle_neighbours(&self, tri: usize) -> Vec<usize> {
+        let mut nbhrs = Vec::new();
+        for e in self.triangle_edges[tri] {
+            for t in self.edge_triangles[e].iter() {
+                if *t != tri && !nbhrs.contains(t) { nbhrs.push(*t); }
+            }
+        }
+        nbhrs
+    }
+
+    /// Indices of the four faces of tetrahedron `tet`, in the order of `Tetrahedron::faces`.
+    pub fn tetrahedron_triangles(&self, tet: usize) -> [usize; 4] {
+        self.tetrahedron_triangles[tet]
+    }
+
+    /// Indices of the tetrahedra having triangle `tri` as a face.
+    pub fn triangle_tetrahedra(&self, tri: usize) -> &[usize] {
+        &self.triangle_tetrahedra[tri]
+    }
+
+    /// Indices of the edges lying on the boundary of the surface, i.e. belonging to exactly one
+    /// triangle.
+    pub fn boundary_edges(&self) -> Vec<usize> {
+        (0..self.edges.len()).filter(|e| self.edge_triangles[*e].len() == 1).collect()
+    }
+
+    /// Indices of the triangles lying on the boundary of the volume, i.e. belonging to exactly
+    /// one tetrahedron.
+    pub fn boundary_triangles(&self) -> Vec<usize> {
+        (0..self.triangles.len()).filter(|t| self.triangle_tetrahedra[*t].len() == 1).collect()
+    }
+
+    /// The boundary operator taking `dim`-chains to (`dim`-1)-chains, as a sparse signed
+    /// incidence matrix with a row per (`dim`-1)-simplex and a column per `dim`-simplex. Edge
+    /// coefficients are relative to the edges as stored, so the boundary of `Edge(a, b)` is `b`
+    /// minus `a`. Triangle and tetrahedron coefficients are relative to their lexicographically
+    /// sorted vertices, and do not depend on the order the vertices are stored in.
+    pub fn boundary_operator(&self, dim: u32) -> SparseMatrix {
+        let rows = match dim {
+            1 => (0..self.vertices.len())
+                .map(|v| self.vertex_edges[v].iter().map(|e| {
+                    (*e, if v == self.edges[*e].1 { 1.0 } else { -1.0 })
+                }).collect())
+                .collect(),
+            2 => (0..self.edges.len())
+                .map(|e| self.edge_triangles[e].iter().map(|t| {
+                    // Sign on the lexicographically sorted edge, then on the stored one.
+                    let mut edge = self.edges[e];
+                    edge.induce_orientation(&self.triangles[*t]);
+                    let sign = if edge.orientation() == self.triangles[*t].orientation() { 1.0 } else { -1.0 };
+                    (*t, if self.edges[e].orientation() == Orientation::CCW { sign } else { -sign })
+                }).collect())
+                .collect(),
+            3 => (0..self.triangles.len())
+                .map(|t| self.triangle_tetrahedra[t].iter().map(|tet| {
+                    let mut tri = self.triangles[t];
+                    tri.induce_orientation(&self.tetrahedra[*tet]);
+                    (*tet, if tri.orientation() == self.tetrahedra[*tet].orientation() { 1.0 } else { -1.0 })
+                }).collect())
+                .collect(),
+            _ => panic!("unsupported dimension."),
+        };
+
+        SparseMatrix::from_rows(self.num_simplices(dim), rows)
+    }
+
+    /// A 64-bit FNV-1a hash of the vertex positions and simplices, used to check that a chain
+    /// read back from disk belongs to this mesh.
+    pub fn fingerprint(&self) -> u64 {
+        let mut hash: u64 = 0xcbf29ce484222325;
+        let mut feed = |x: u64| {
+            for b in x.to_le_bytes() {
+                hash ^= b as u64;
+                hash = hash.wrapping_mul(0x100000001b3);
+            }
+        };
+
+        feed(self.vertices.len() as u64);
+        for v in self.vertices.iter() { feed(v.x.to_bits()); feed(v.y.to_bits()); feed(v.z.to_bits()); }
+        feed(self.edges.len() as u64);
+        for e in self.edges.iter() { feed(e.0 as u64); feed(e.1 as u64); }
+        feed(self.triangles.len() as u64);
+        for t in self.triangles.iter() { feed(t.0 as u64); feed(t.1 as u64); feed(t.2 as u64); }
+        feed(self.tetrahedra.len() as u64);
+        for t in self.tetrahedra.iter() { feed(t.0 as u64); feed(t.1 as u64); feed(t.2 as u64); feed(t.3 as u64); }
+
+        hash
+    }
+
+    /// Number of simplices of the given dimension.
+    pub fn num_simplices(&self, dim: u32) -> usize {
+        match dim {
+            0 => self.vertices.len(),
+            1 => self.edges.len(),
+            2 => self.triangles.len(),
+            3 => self.tetrahedra.len(),
+            _ => panic!("unsupported dimension.")
+        }
+    }
+
+    /// Loads a mesh from disk, picking the format from the file extension. OFF is assumed when
+    /// the extension is missing or unknown.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Mesh, String> {
+        let ext = path.as_ref().extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_ascii_lowercase());
+
+        match ext.as_deref() {
+            Some("obj") => Wavefront::load(path)?.surface(),
+            Some("ply") => Ok(Mesh::load_ply(path)?.0),
+            Some("msh") => Mesh::load_msh(path),
+            Some("node") | Some("ele") => Mesh::load_tetgen(path),
+            _ => Mesh::load_off(path).map_err(|e| e.to_string()),
+        }
+    }
+
+    /// Saves the mesh, picking the format from the file extension. OFF is assumed when the
+    /// extension is missing or unknown.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
+        let ext = path.as_ref().extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_ascii_lowercase());
+
+        match ext.as_deref() {
+            Some("obj") => self.save_obj(path, &[]),
+            Some("ply") => self.save_ply(path, PlyFormat::Ascii, &[]),
+            _ => self.save_off(path),
+        }
+    }
+
+    /// Returns the index of the vertex closest to `pt` (the lowest one on ties), or `None` if the
+    /// mesh has no vertices.
+    pub fn closest_vertex(&self, pt: &Vector3<f64>) -> Option<usize> {
+        let tree = self.vertex_tree.get_or_init(|| KdTree::new(&self.vertices));
+        tree.nearest(pt).map(|(idx, _)| idx)
+    }
+
+    /// Returns the point of the surface closest to `pt` together with the index of the triangle
+    /// it lies on, or `None` if the mesh has no triangles.
+    pub fn closest_point(&self, pt: &Vector3<f64>) -> Option<(Vector3<f64>, usize)> {
+        let tree = self.triangle_tree.get_or_init(|| {
+            Bvh::new(self.triangles.iter().map(|t| [self.vertices[t.0], self.vertices[t.1], self.vertices[t.2]]).collect())
+        });
+        tree.nearest(pt).map(|(q, idx, _)| (q, idx))
+    }
+
+    /// A typical maximum distance between a point and the surface for the point to be snapped
+    /// onto the mesh (see `Current::load_within`): the mean edge length.
+    pub fn snap_distance(&self) -> f64 {
+        if self.edges.is_empty() { return 0.0; }
+        self.edges.iter().map(|e| e.length(self)).sum::<f64>() / self.edges.len() as f64
+    }
     
+    /// Orients planar meshes facing up (+y), see `OrientationMode::Plane`.
     pub fn orient2d(&mut self) -> Result<(), String> {
+        self.orient_by(OrientationMode::Plane(AxisPlane::XZ)).map(|_| ()).map_err(|e| e.to_string())
+    }
+
+    /// Orients the triangles following `mode`, returning the number of connected components.
+    ///
+    /// With `Outward` the mesh is first oriented consistently (see `orient`), which fails on
+    /// non-orientable surfaces, and the edges are left oriented lexicographically. The other modes
+    /// orient each triangle on its own and induce the orientation of each edge from the first
+    /// triangle it belongs to. Triangles perpendicular to the reference normal are left as they
+    /// are.
+    pub fn orient_by(&mut self, mode: OrientationMode) -> Result<usize, OrientationError> {
+        let reference: Vec<Vector3<f64>> = match mode {
+            OrientationMode::Outward => {
+                let components = self.orient()?;
+                let (labels, _) = self.face_components();
+
+                // Signed volume of each component, relative to its centroid so that it does not
+                // depend on where the mesh is for surfaces that are not quite closed.
+                let mut centroid = vec![(Vector3::zeros(), 0.0); components];
+                for (t, tri) in self.triangles.iter().enumerate() {
+                    let c = &mut centroid[labels[t]];
+                    c.0 += self.vertices[tri.0] + self.vertices[tri.1] + self.vertices[tri.2];
+                    c.1 += 3.0;
+                }
+                let mut volume = vec![0.0; components];
+                for (t, tri) in self.triangles.iter().enumerate() {
+                    let o = centroid[labels[t]].0 / centroid[labels[t]].1;
+                    let (a, b, c) = (self.vertices[tri.0] - o, self.vertices[tri.1] - o, self.vertices[tri.2] - o);
+                    volume[labels[t]] += a.dot(&b.cross(&c)) / 6.0;
+                }
+
+                for (t, tri) in self.triangles.iter_mut().enumerate() {
+                    if volume[labels[t]] < 0.0 { tri.swap_orientation(); }
+                }
+                return Ok(components);
+            },
+            OrientationMode::Plane(plane) => vec![plane.normal(); self.triangles.len()],
+            OrientationMode::Direction(dir) => vec![dir; self.triangles.len()],
+            OrientationMode::VertexNormals(normals) => {
+                if normals.len() != self.vertices.len() {
+                    return Err(OrientationError::NormalCount { expected: self.vertices.len(), found: normals.len() });
+                }
+                self.triangles.iter().map(|t| normals[t.0] + normals[t.1] + normals[t.2]).collect()
+            },
+        };
+
         // Orient all edges lexicographically.
         for edge in self.edges.iter_mut() {
             let a = edge.0; let b = edge.1;
             edge.0 = a.min(b); edge.1 = a.max(b);
         }
 
-        // Orient the triangles by signed area.
-        for i in 0..self.triangles.len() {
+        // Orient the triangles along the reference normals.
+        for (i, r) in reference.iter().enumerate() {
             self.triangles[i].orient(Orientation::CCW); 
 
-            if self.triangles[i].signed_area(self) < 0.0 {
+            if self.triangles[i].normal(self).dot(r) < 0.0 {
                 self.triangles[i].swap_orientation();
             }
         }
@@ -184,7 +624,7 @@ impl Mesh {
         // Induce orientation onto edges.
         let mut visited = vec![false; self.edges.len()];
         for tri in 0..self.triangles.len() {
-            let edges = self._edges(tri);
+            let edges = self.triangle_edges(tri);
             for edge in edges.iter() {
                 if visited[*edge] { continue; }
                 self.edges[*edge].induce_orientation(&self.triangles[tri]);
@@ -192,10 +632,38 @@ impl Mesh {
             }
         }
 
-        Ok(())        
+        Ok(self.face_components().1)
     }
 
-    pub fn orient(&mut self) -> Result<(), String> {
+    /// Labels each triangle with its connected component (triangles sharing an edge are
+    /// connected), returning the labels and the number of components.
+    fn face_components(&self) -> (Vec<usize>, usize) {
+        let mut labels = vec![usize::MAX; self.triangles.len()];
+        let mut count = 0;
+        for seed in 0..self.triangles.len() {
+            if labels[seed] != usize::MAX { continue; }
+            labels[seed] = count;
+            let mut stack = vec![seed];
+            while let Some(tri) = stack.pop() {
+                for nbhr in self.triangle_neighbours(tri) {
+                    if labels[nbhr] == usize::MAX {
+                        labels[nbhr] = count;
+                        stack.push(nbhr);
+                    }
+                }
+            }
+            count += 1;
+        }
+
+        (labels, count)
+    }
+
+    /// Orients the triangles consistently, so that neighbouring triangles induce opposite
+    /// orientations on their shared edge, and orients the edges lexicographically. Every connected
+    /// component is oriented separately, starting from its lowest triangle; the number of
+    /// components is returned. Fails on non-orientable surfaces with a cycle of faces that cannot
+    /// be oriented consistently.
+    pub fn orient(&mut self) -> Result<usize, OrientationError> {
         // Orient all edges lexicographically.
         for edge in self.edges.iter_mut() {
             let a = edge.0; let b = edge.1;
@@ -205,83 +673,237 @@ impl Mesh {
         // Orient all triangles lexicographically (CCW) to start.
         for tri in self.triangles.iter_mut() { tri.orient(Orientation::CCW); }
 
-        // Keep track of what triangles we have oriented.
+        // Keep track of what triangles we have oriented, and from which neighbour.
         let mut visited = vec![false; self.triangles.len()]; 
-
-        // Fix the first triangle's orientation and add it to the FIFO queue.
-        visited[0] = true;
-        let mut queue = VecDeque::new();
-        queue.push_back(0);
-        
-        // Intermediate step: propogate the orientation of face tri to its neighbors.
-        while let Some(tri) = queue.pop_front() {
-            // Get tri's edges and what oriention tri induces onto each edge.
-            let edges = self._edges(tri);
-            let orientations: Vec<Orientation> = edges.iter()
-                .map(|i| { 
-                    let mut e = self.edges[*i].clone(); 
-                    e.induce_orientation(&self.triangles[tri]); 
-                    e.orientation() 
-                })
-                .collect();
-
-            // Determine the orientation of each neighboring face.
-            for nbhr in self._nbhrs(tri) {
-                // For each shared edge (should be 1), ensure that tri and nbhr induce opposite
-                // orientations.
+        let mut parent = vec![None; self.triangles.len()];
+        let mut components = 0;
+
+        for seed in 0..self.triangles.len() {
+            if visited[seed] { continue; }
+            components += 1;
+
+            // Fix the seed's orientation and add it to the FIFO queue.
+            visited[seed] = true;
+            let mut queue = VecDeque::new();
+            queue.push_back(seed);
+
+            // Intermediate step: propogate the orientation of face tri to its neighbors.
+            while let Some(tri) = queue.pop_front() {
+                // Get tri's edges and what oriention tri induces onto each edge.
+                let edges = self.triangle_edges(tri);
+                let orientations: Vec<Orientation> = edges.iter()
+                    .map(|i| { 
+                        let mut e = self.edges[*i]; 
+                        e.induce_orientation(&self.triangles[tri]); 
+                        e.orientation() 
+                    })
+                    .collect();
+
+                // Determine the orientation of each neighboring face. Both triangles sharing an
+                // edge must induce opposite orientations on it.
                 for i in 0..edges.len() {
-                    if !self.triangles[nbhr].is_face(&self.edges[edges[i]]) { continue; }
-
-                    let mut edge_copy = self.edges[edges[i]].clone();
-                    edge_copy.induce_orientation(&self.triangles[nbhr]);
+                    for nbhr in self.edge_triangles(edges[i]).to_vec() {
+                        if nbhr == tri { continue; }
+
+                        let mut edge_copy = self.edges[edges[i]];
+                        edge_copy.induce_orientation(&self.triangles[nbhr]);
+
+                        // If both triangles induce the same oriention, swap nbhr's orientation.
+                        if orientations[i] == edge_copy.orientation() {
+                            if visited[nbhr] { // We've already oriented this triangle and now we
+                                               // need to change it's orientation!
+                                return Err(OrientationError::NonOrientable { cycle: face_cycle(&parent, tri, nbhr) });
+                            }
+                            self.triangles[nbhr].swap_orientation();
+                        }
 
-                    // If both triangles induce the same oriention, swap nbhr's orientation.
-                    if orientations[i] == edge_copy.orientation() {
-                        if visited[nbhr] { // We've already oriented this triangle and now we need
-                                           // to change it's orientation!
-                            return Err(format!("{:?} requires both CW and CCW orientation.", self.triangles[nbhr]));
+                        // Add each unvisited neigbhor to our queue
+                        if !visited[nbhr] { 
+                            visited[nbhr] = true;
+                            parent[nbhr] = Some(tri);
+                            queue.push_back(nbhr);
                         }
-                        self.triangles[nbhr].swap_orientation();
                     }
                 }
-                                
-                // Add each unvisited neigbhor to our queue
-                if !visited[nbhr] { 
-                    visited[nbhr] = true;
-                    queue.push_back(nbhr);
-                }
             }
         }
 
-        Ok(())
+        Ok(components)
+    }
+}
+
+/// The cycle of faces closed by the neighbours `a` and `b` in the breadth-first search tree given
+/// by `parent`: from `a` up to the common ancestor of `a` and `b`, then down to `b`.
+fn face_cycle(parent: &[Option<usize>], a: usize, b: usize) -> Vec<usize> {
+    let ancestors = |mut x: usize| {
+        let mut path = vec![x];
+        while let Some(p) = parent[x] { path.push(p); x = p; }
+        path
+    };
+
+    // Both paths end at the root. Strip their common part, keeping the lowest common ancestor.
+    let mut up = ancestors(a);
+    let mut down = ancestors(b);
+    while up.len() > 1 && down.len() > 1 && up[up.len() - 2] == down[down.len() - 2] {
+        up.pop();
+        down.pop();
+    }
+    down.pop();
+    up.extend(down.into_iter().rev());
+    up
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::generate::{grid, Diagonal};
+    use crate::prelude::Current;
+    use std::rc::Rc;
+
+    #[test]
+    fn find_simplices() {
+        let mesh = grid(2.0, 2.0, 2, 2, Diagonal::Forward);
+        for (idx, e) in mesh.edges.iter().enumerate() {
+            assert_eq!(mesh.find_edge(e.0, e.1), Some((idx, 1.0)));
+            assert_eq!(mesh.find_edge(e.1, e.0), Some((idx, -1.0)));
+        }
+        for (idx, t) in mesh.triangles.iter().enumerate() {
+            assert_eq!(mesh.find_triangle(t.0, t.1, t.2), Some((idx, 1.0)));
+            assert_eq!(mesh.find_triangle(t.1, t.2, t.0), Some((idx, 1.0)));
+            assert_eq!(mesh.find_triangle(t.2, t.0, t.1), Some((idx, 1.0)));
+            assert_eq!(mesh.find_triangle(t.1, t.0, t.2), Some((idx, -1.0)));
+            assert_eq!(mesh.find_triangle(t.0, t.2, t.1), Some((idx, -1.0)));
+        }
+        assert_eq!(mesh.find_edge(0, 8), None);
+        assert_eq!(mesh.find_triangle(0, 1, 8), None);
     }
 
-    fn _edges(&self, tri: usize) -> [usize; 3] {
-        let mut edges = [0; 3];
-        let mut idx = 0;
-        for i in 0..self.edges.len() {
-            if self.triangles[tri].is_face(&self.edges[i]) {
-                edges[idx] = i;
-                idx += 1;
+    #[test]
+    fn incidence_queries() {
+        let mesh = grid(2.0, 2.0, 2, 2, Diagonal::Forward);
+        assert_eq!(mesh.one_ring(4).len(), 6);
+        assert_eq!(mesh.one_ring(0).len(), 3);
+        assert_eq!(mesh.one_ring(2).len(), 2);
+        assert_eq!(mesh.vertex_triangles(4).len(), 6);
+        assert_eq!(mesh.vertex_triangles(2).len(), 1);
+        assert_eq!(mesh.boundary_edges().len(), 8);
+        assert!(mesh.boundary_triangles().is_empty());
+
+        for (t, tri) in mesh.triangles.iter().enumerate() {
+            let sides = [(tri.0, tri.1), (tri.0, tri.2), (tri.1, tri.2)];
+            for (e, (a, b)) in mesh.triangle_edges(t).into_iter().zip(sides) {
+                assert_eq!(mesh.find_edge(a, b).map(|(idx, _)| idx), Some(e));
+                assert!(mesh.edge_triangles(e).contains(&t));
+            }
+            let boundary = mesh.triangle_edges(t).iter().filter(|e| mesh.edge_triangles(**e).len() == 1).count();
+            assert_eq!(mesh.triangle_neighbours(t).len(), 3 - boundary);
+            for n in mesh.triangle_neighbours(t) {
+                assert!(mesh.triangle_neighbours(n).contains(&t));
+            }
+        }
+        for v in 0..mesh.vertices.len() {
+            for e in mesh.vertex_edges(v) {
+                assert!(mesh.edges[*e].0 == v || mesh.edges[*e].1 == v);
             }
         }
+    }
 
-        edges
+    /// The boundary of the boundary of each `dim`-simplex.
+    fn boundary_of_boundary(mesh: &Mesh, dim: u32) -> Vec<Vec<f64>> {
+        let inner = mesh.boundary_operator(dim);
+        let outer = mesh.boundary_operator(dim - 1);
+        (0..mesh.num_simplices(dim)).map(|i| {
+            let mut x = vec![0.0; mesh.num_simplices(dim)];
+            x[i] = 1.0;
+            outer.mul_vec(&inner.mul_vec(&x))
+        }).collect()
     }
 
-    fn _nbhrs(&self, tri: usize) -> Vec<usize> {
-        let edges: Vec<&Edge> = self._edges(tri).iter().map(|i| &self.edges[*i]).collect();
+    #[test]
+    fn edge_boundary_follows_stored_orientation() {
+        let vertices = vec![Vector3::zeros(), Vector3::x(), Vector3::z()];
+        let edges = vec![Edge(1, 0), Edge(1, 2), Edge(2, 0)];
+        let mesh = Mesh::from_parts(vertices, edges, vec![Triangle(0, 2, 1)], Vec::new()).unwrap();
 
-        let mut nbhrs = Vec::new();
-        for i in 0..self.triangles.len() {
-            if i == tri { continue; }
+        let b = mesh.boundary_operator(1);
+        assert_eq!((b.get(0, 0), b.get(1, 0)), (1.0, -1.0));
+        assert_eq!((b.get(1, 1), b.get(2, 1)), (-1.0, 1.0));
+        assert!(boundary_of_boundary(&mesh, 2).iter().all(|x| x.iter().all(|c| *c == 0.0)));
+    }
 
-            if self.triangles[i].is_face(edges[0])
-                || self.triangles[i].is_face(edges[1])
-                || self.triangles[i].is_face(edges[2]) {
-                nbhrs.push(i);
-            }
+    #[test]
+    fn boundary_of_boundary_vanishes() {
+        let mut mesh = grid(2.0, 2.0, 3, 3, Diagonal::Alternating);
+        mesh.orient2d().unwrap();
+        assert!(boundary_of_boundary(&mesh, 2).iter().all(|x| x.iter().all(|c| *c == 0.0)));
+
+        let vertices = vec![Vector3::zeros(), Vector3::x(), Vector3::y(), Vector3::z(), -Vector3::z()];
+        let mesh = Mesh::from_tetrahedra(vertices, vec![Tetrahedron(0, 1, 2, 3), Tetrahedron(0, 2, 1, 4)]);
+        assert!(boundary_of_boundary(&mesh, 2).iter().all(|x| x.iter().all(|c| *c == 0.0)));
+        assert!(boundary_of_boundary(&mesh, 3).iter().all(|x| x.iter().all(|c| *c == 0.0)));
+    }
+
+    #[test]
+    fn path_boundary_after_orient2d() {
+        let mut mesh = grid(2.0, 2.0, 3, 3, Diagonal::Alternating);
+        mesh.orient2d().unwrap();
+        let mesh = Rc::new(mesh);
+        assert_eq!(mesh.find_edge(1, 5).map(|e| e.1), Some(-1.0));
+
+        let path = Current { mesh: mesh.clone(), path: vec![1, 5, 9, 13] }.as_chain().unwrap();
+        let boundary = path.boundary().unwrap();
+        for (v, c) in boundary.coeff.iter().enumerate() {
+            assert_eq!(*c, if v == 13 { 1.0 } else if v == 1 { -1.0 } else { 0.0 });
+        }
+    }
+
+    #[test]
+    fn vertex_normal_count_is_checked() {
+        let mut mesh = grid(1.0, 1.0, 1, 1, Diagonal::Forward);
+        assert_eq!(mesh.orient_by(OrientationMode::VertexNormals(vec![])), Err(OrientationError::NormalCount { expected: 4, found: 0 }));
+    }
+
+    #[test]
+    fn components_are_oriented_separately() {
+        let vertices = (0..8).map(|i| Vector3::new((i % 4 / 2) as f64 + 5.0 * (i / 4) as f64, 0.0, (i % 2) as f64)).collect();
+        let triangles = vec![Triangle(0, 1, 2), Triangle(5, 7, 6), Triangle(3, 2, 1), Triangle(4, 5, 6)];
+        let mut mesh = Mesh::new(vertices, triangles);
+        assert_eq!(mesh.orient(), Ok(2));
+    }
+
+    #[test]
+    fn mobius_strip_is_not_orientable() {
+        // A strip of `n` quads whose two sides are swapped when closing it up.
+        let n = 6;
+        let mut vertices = Vec::new();
+        for i in 0..n {
+            let u = 2.0 * std::f64::consts::PI * i as f64 / n as f64;
+            let radial = Vector3::new(u.cos(), 0.0, u.sin());
+            let across = radial * (u / 2.0).cos() + Vector3::new(0.0, (u / 2.0).sin(), 0.0);
+            vertices.push(radial * 2.0 + across * 0.5);
+            vertices.push(radial * 2.0 - across * 0.5);
+        }
+        let (top, bottom) = (|i: usize| 2 * (i % n), |i: usize| 2 * (i % n) + 1);
+        let mut triangles = Vec::new();
+        for i in 0..n - 1 {
+            triangles.push(Triangle(top(i), bottom(i), top(i + 1)));
+            triangles.push(Triangle(bottom(i), bottom(i + 1), top(i + 1)));
+        }
+        triangles.push(Triangle(top(n - 1), bottom(n - 1), bottom(n)));
+        triangles.push(Triangle(bottom(n - 1), top(n), bottom(n)));
+
+        let mut mesh = Mesh::new(vertices, triangles);
+        let cycle = match mesh.orient() {
+            Err(OrientationError::NonOrientable { cycle }) => cycle,
+            other => panic!("expected a non-orientable surface, got {:?}", other),
+        };
+        assert!(cycle.len() >= 3);
+        let mut distinct = cycle.clone();
+        distinct.sort();
+        distinct.dedup();
+        assert_eq!(distinct.len(), cycle.len());
+        for (i, t) in cycle.iter().enumerate() {
+            assert!(mesh.triangle_neighbours(*t).contains(&cycle[(i + 1) % cycle.len()]));
         }
-        nbhrs
     }
 }
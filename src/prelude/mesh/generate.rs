@@ -0,0 +1,233 @@
+//! Procedural meshes. Every generator winds its triangles consistently: planar meshes face up
+//! (+y) and closed or tubular surfaces face outwards.
+
+use super::{Mesh, Triangle};
+use nalgebra::Vector3;
+use std::collections::HashMap;
+use std::f64::consts::PI;
+
+/// How each cell of a `grid` is split into triangles.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Diagonal {
+    /// Every cell is split along the diagonal from its (-x, -z) to its (+x, +z) corner.
+    Forward,
+    /// Every cell is split along the diagonal from its (+x, -z) to its (-x, +z) corner.
+    Backward,
+    /// Alternates between `Forward` and `Backward` like a checkerboard.
+    Alternating,
+    /// Adds a vertex at the centre of every cell and splits it into 4 triangles.
+    Cross,
+}
+
+/// A `width` by `depth` rectangle in the XZ plane, centred on the origin, split into `nx` by `nz`
+/// cells.
+pub fn grid(width: f64, depth: f64, nx: usize, nz: usize, diagonal: Diagonal) -> Mesh {
+    assert!(nx > 0 && nz > 0, "a grid needs at least one cell in each direction.");
+
+    let mut vertices = Vec::new();
+    for j in 0..=nz {
+        for i in 0..=nx {
+            let x = width * (i as f64 / nx as f64 - 0.5);
+            let z = depth * (j as f64 / nz as f64 - 0.5);
+            vertices.push(Vector3::new(x, 0.0, z));
+        }
+    }
+
+    let idx = |i: usize, j: usize| j * (nx + 1) + i;
+    let mut triangles = Vec::new();
+    for j in 0..nz {
+        for i in 0..nx {
+            // Corners of the cell, listed so that (a, c, b) faces +y.
+            let a = idx(i, j);
+            let b = idx(i + 1, j);
+            let c = idx(i, j + 1);
+            let d = idx(i + 1, j + 1);
+
+            let forward = match diagonal {
+                Diagonal::Forward => true,
+                Diagonal::Backward => false,
+                Diagonal::Alternating => (i + j) % 2 == 0,
+                Diagonal::Cross => {
+                    let m = vertices.len();
+                    vertices.push((vertices[a] + vertices[b] + vertices[c] + vertices[d]) / 4.0);
+                    triangles.push(Triangle(a, m, b));
+                    triangles.push(Triangle(b, m, d));
+                    triangles.push(Triangle(d, m, c));
+                    triangles.push(Triangle(c, m, a));
+                    continue;
+                }
+            };
+
+            if forward {
+                triangles.push(Triangle(a, d, b));
+                triangles.push(Triangle(a, c, d));
+            } else {
+                triangles.push(Triangle(a, c, b));
+                triangles.push(Triangle(b, c, d));
+            }
+        }
+    }
+
+    Mesh::new(vertices, triangles)
+}
+
+/// A sphere of the given radius centred on the origin, made by subdividing each face of an
+/// icosahedron `subdivisions` times (each subdivision splits a triangle into 4).
+pub fn icosphere(radius: f64, subdivisions: usize) -> Mesh {
+    let t = (1.0 + 5.0_f64.sqrt()) / 2.0;
+    let mut vertices: Vec<Vector3<f64>> = [
+        (-1.0, t, 0.0), (1.0, t, 0.0), (-1.0, -t, 0.0), (1.0, -t, 0.0),
+        (0.0, -1.0, t), (0.0, 1.0, t), (0.0, -1.0, -t), (0.0, 1.0, -t),
+        (t, 0.0, -1.0), (t, 0.0, 1.0), (-t, 0.0, -1.0), (-t, 0.0, 1.0),
+    ].iter().map(|(x, y, z)| Vector3::new(*x, *y, *z).normalize()).collect();
+
+    let mut triangles = vec![
+        Triangle(0, 11, 5), Triangle(0, 5, 1), Triangle(0, 1, 7), Triangle(0, 7, 10), Triangle(0, 10, 11),
+        Triangle(1, 5, 9), Triangle(5, 11, 4), Triangle(11, 10, 2), Triangle(10, 7, 6), Triangle(7, 1, 8),
+        Triangle(3, 9, 4), Triangle(3, 4, 2), Triangle(3, 2, 6), Triangle(3, 6, 8), Triangle(3, 8, 9),
+        Triangle(4, 9, 5), Triangle(2, 4, 11), Triangle(6, 2, 10), Triangle(8, 6, 7), Triangle(9, 8, 1),
+    ];
+
+    for _ in 0..subdivisions {
+        let mut midpoints: HashMap<(usize, usize), usize> = HashMap::new();
+        let mut midpoint = |a: usize, b: usize, vertices: &mut Vec<Vector3<f64>>| {
+            *midpoints.entry((a.min(b), a.max(b))).or_insert_with(|| {
+                vertices.push(((vertices[a] + vertices[b]) / 2.0).normalize());
+                vertices.len() - 1
+            })
+        };
+
+        let mut refined = Vec::with_capacity(4 * triangles.len());
+        for &Triangle(a, b, c) in triangles.iter() {
+            let ab = midpoint(a, b, &mut vertices);
+            let bc = midpoint(b, c, &mut vertices);
+            let ca = midpoint(c, a, &mut vertices);
+            refined.push(Triangle(a, ab, ca));
+            refined.push(Triangle(b, bc, ab));
+            refined.push(Triangle(c, ca, bc));
+            refined.push(Triangle(ab, bc, ca));
+        }
+        triangles = refined;
+    }
+
+    for v in vertices.iter_mut() { *v *= radius; }
+    Mesh::new(vertices, triangles)
+}
+
+/// A torus centred on the origin around the y-axis. `major` is the distance from the centre to
+/// the middle of the tube and `minor` the radius of the tube; `segments` counts the subdivisions
+/// around the y-axis and `rings` those around the tube.
+pub fn torus(major: f64, minor: f64, segments: usize, rings: usize) -> Mesh {
+    assert!(segments >= 3 && rings >= 3, "a torus needs at least 3 segments and 3 rings.");
+
+    let mut vertices = Vec::with_capacity(segments * rings);
+    for i in 0..segments {
+        let theta = 2.0 * PI * i as f64 / segments as f64;
+        for j in 0..rings {
+            let phi = 2.0 * PI * j as f64 / rings as f64;
+            let r = major + minor * phi.cos();
+            vertices.push(Vector3::new(r * theta.cos(), minor * phi.sin(), r * theta.sin()));
+        }
+    }
+
+    let idx = |i: usize, j: usize| (i % segments) * rings + (j % rings);
+    let mut triangles = Vec::with_capacity(2 * segments * rings);
+    for i in 0..segments {
+        for j in 0..rings {
+            let a = idx(i, j);
+            let b = idx(i + 1, j);
+            let c = idx(i, j + 1);
+            let d = idx(i + 1, j + 1);
+            triangles.push(Triangle(a, c, b));
+            triangles.push(Triangle(b, c, d));
+        }
+    }
+
+    Mesh::new(vertices, triangles)
+}
+
+/// An open cylinder (no caps) of the given radius around the y-axis, spanning `height` centred
+/// on the origin. `segments` counts the subdivisions around the axis and `rings` those along it.
+pub fn cylinder(radius: f64, height: f64, segments: usize, rings: usize) -> Mesh {
+    assert!(segments >= 3 && rings >= 1, "a cylinder needs at least 3 segments and 1 ring.");
+
+    let mut vertices = Vec::with_capacity(segments * (rings + 1));
+    for j in 0..=rings {
+        let y = height * (j as f64 / rings as f64 - 0.5);
+        for i in 0..segments {
+            let theta = 2.0 * PI * i as f64 / segments as f64;
+            vertices.push(Vector3::new(radius * theta.cos(), y, radius * theta.sin()));
+        }
+    }
+
+    let idx = |i: usize, j: usize| j * segments + (i % segments);
+    let mut triangles = Vec::with_capacity(2 * segments * rings);
+    for j in 0..rings {
+        for i in 0..segments {
+            let a = idx(i, j);
+            let b = idx(i + 1, j);
+            let c = idx(i, j + 1);
+            let d = idx(i + 1, j + 1);
+            triangles.push(Triangle(a, c, b));
+            triangles.push(Triangle(b, c, d));
+        }
+    }
+
+    Mesh::new(vertices, triangles)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prelude::OrientationMode;
+
+    const DIAGONALS: [Diagonal; 4] = [Diagonal::Forward, Diagonal::Backward, Diagonal::Alternating, Diagonal::Cross];
+
+    #[test]
+    fn counts() {
+        for diagonal in DIAGONALS {
+            let mesh = grid(2.0, 3.0, 4, 5, diagonal);
+            let cells = if diagonal == Diagonal::Cross { 4 * 5 } else { 0 };
+            let per_cell = if diagonal == Diagonal::Cross { 4 } else { 2 };
+            assert_eq!(mesh.vertices.len(), 5 * 6 + cells);
+            assert_eq!(mesh.triangles.len(), per_cell * 4 * 5);
+        }
+        for s in 0..3 {
+            let mesh = icosphere(1.0, s);
+            assert_eq!(mesh.vertices.len(), 10 * 4usize.pow(s as u32) + 2);
+            assert_eq!(mesh.triangles.len(), 20 * 4usize.pow(s as u32));
+            assert_eq!(mesh.edges.len(), 30 * 4usize.pow(s as u32));
+        }
+        let mesh = torus(2.0, 0.5, 8, 5);
+        assert_eq!((mesh.vertices.len(), mesh.triangles.len(), mesh.edges.len()), (40, 80, 120));
+        let mesh = cylinder(1.0, 2.0, 8, 3);
+        assert_eq!((mesh.vertices.len(), mesh.triangles.len(), mesh.edges.len()), (32, 48, 80));
+    }
+
+    #[test]
+    fn grids_face_up() {
+        for diagonal in DIAGONALS {
+            let mesh = grid(2.0, 3.0, 4, 5, diagonal);
+            assert!(mesh.triangles.iter().all(|t| t.normal(&mesh).y > 0.0), "{:?}", diagonal);
+        }
+    }
+
+    #[test]
+    fn consistently_oriented() {
+        // `orient` may flip everything, but flips nothing on its own when the winding is consistent.
+        for mut mesh in [grid(2.0, 2.0, 3, 3, Diagonal::Alternating), icosphere(1.0, 2), torus(2.0, 0.5, 8, 5), cylinder(1.0, 2.0, 8, 3)] {
+            let before: Vec<_> = mesh.triangles.iter().map(|t| t.orientation()).collect();
+            assert_eq!(mesh.orient(), Ok(1));
+            let same = mesh.triangles.iter().zip(before.iter()).filter(|(t, o)| t.orientation() == **o).count();
+            assert!(same == 0 || same == before.len());
+        }
+
+        // Closed meshes already face outwards.
+        for mut mesh in [icosphere(1.0, 2), torus(2.0, 0.5, 8, 5)] {
+            let before: Vec<_> = mesh.triangles.iter().map(|t| t.orientation()).collect();
+            mesh.orient_by(OrientationMode::Outward).unwrap();
+            let after: Vec<_> = mesh.triangles.iter().map(|t| t.orientation()).collect();
+            assert_eq!(before, after);
+        }
+    }
+}
@@ -0,0 +1,276 @@
+//! Spatial indices for nearest neighbour queries: a k-d tree over points and a bounding volume
+//! hierarchy over triangles. Ties are broken towards the lowest index, so queries do not depend
+//! on the order the tree visits its nodes.
+
+use nalgebra::Vector3;
+
+/// A k-d tree over a set of points.
+pub struct KdTree {
+    points: Vec<Vector3<f64>>,
+    /// Point indices, arranged so that the middle of every range is the splitting point of the
+    /// subtree covering that range.
+    order: Vec<usize>,
+}
+
+impl KdTree {
+    pub fn new(points: &[Vector3<f64>]) -> Self {
+        let mut order: Vec<usize> = (0..points.len()).collect();
+        build_kdtree(points, &mut order, 0);
+        KdTree { points: points.to_vec(), order }
+    }
+
+    /// Returns the index of the point closest to `pt` and its squared distance, or `None` if the
+    /// tree is empty.
+    pub fn nearest(&self, pt: &Vector3<f64>) -> Option<(usize, f64)> {
+        let mut best = None;
+        self.search(pt, 0, self.order.len(), 0, &mut best);
+        best
+    }
+
+    fn search(&self, pt: &Vector3<f64>, lo: usize, hi: usize, axis: usize, best: &mut Option<(usize, f64)>) {
+        if lo >= hi { return; }
+
+        let mid = lo + (hi - lo) / 2;
+        let idx = self.order[mid];
+        let d2 = (pt - self.points[idx]).norm_squared();
+        if is_better(idx, d2, *best) { *best = Some((idx, d2)); }
+
+        // Visit the side containing `pt` first, then the other one if it can hold a closer point.
+        let delta = pt[axis] - self.points[idx][axis];
+        let (near, far) = if delta < 0.0 { ((lo, mid), (mid + 1, hi)) } else { ((mid + 1, hi), (lo, mid)) };
+        self.search(pt, near.0, near.1, (axis + 1) % 3, best);
+        if best.map(|(_, d)| delta * delta <= d).unwrap_or(true) {
+            self.search(pt, far.0, far.1, (axis + 1) % 3, best);
+        }
+    }
+}
+
+fn build_kdtree(points: &[Vector3<f64>], order: &mut [usize], axis: usize) {
+    if order.len() <= 1 { return; }
+
+    let mid = order.len() / 2;
+    order.select_nth_unstable_by(mid, |a, b| points[*a][axis].total_cmp(&points[*b][axis]));
+    let (left, right) = order.split_at_mut(mid);
+    build_kdtree(points, left, (axis + 1) % 3);
+    build_kdtree(points, &mut right[1..], (axis + 1) % 3);
+}
+
+fn is_better(idx: usize, d2: f64, best: Option<(usize, f64)>) -> bool {
+    match best {
+        None => true,
+        Some((i, d)) => d2 < d || (d2 == d && idx < i),
+    }
+}
+
+const BVH_LEAF_SIZE: usize = 4;
+
+struct BvhNode {
+    min: Vector3<f64>,
+    max: Vector3<f64>,
+    /// Children for inner nodes, range of `Bvh::order` for leaves.
+    kind: BvhKind,
+}
+
+enum BvhKind {
+    Inner(usize, usize),
+    Leaf(usize, usize),
+}
+
+/// A bounding volume hierarchy of axis-aligned boxes over a set of triangles.
+pub struct Bvh {
+    triangles: Vec<[Vector3<f64>; 3]>,
+    order: Vec<usize>,
+    nodes: Vec<BvhNode>,
+}
+
+impl Bvh {
+    pub fn new(triangles: Vec<[Vector3<f64>; 3]>) -> Self {
+        let mut bvh = Bvh {
+            order: (0..triangles.len()).collect(),
+            triangles,
+            nodes: Vec::new(),
+        };
+        if !bvh.triangles.is_empty() { bvh.build(0, bvh.triangles.len()); }
+        bvh
+    }
+
+    /// Returns the point of the triangles closest to `pt`, the index of the triangle it lies on
+    /// and its squared distance, or `None` if there are no triangles.
+    pub fn nearest(&self, pt: &Vector3<f64>) -> Option<(Vector3<f64>, usize, f64)> {
+        let mut best: Option<(Vector3<f64>, usize, f64)> = None;
+        let mut stack = vec![0];
+        while let Some(node) = stack.pop() {
+            let Some(node) = self.nodes.get(node) else { break };
+            if let Some((_, _, d)) = best {
+                if box_distance2(pt, &node.min, &node.max) > d { continue; }
+            }
+
+            match node.kind {
+                BvhKind::Inner(left, right) => { stack.push(right); stack.push(left); }
+                BvhKind::Leaf(lo, hi) => {
+                    for &idx in self.order[lo..hi].iter() {
+                        let [a, b, c] = &self.triangles[idx];
+                        let q = closest_point_on_triangle(pt, a, b, c);
+                        let d2 = (pt - q).norm_squared();
+                        if is_better(idx, d2, best.map(|(_, i, d)| (i, d))) { best = Some((q, idx, d2)); }
+                    }
+                }
+            }
+        }
+
+        best
+    }
+
+    fn build(&mut self, lo: usize, hi: usize) -> usize {
+        let mut min = Vector3::repeat(f64::INFINITY);
+        let mut max = Vector3::repeat(f64::NEG_INFINITY);
+        for &idx in self.order[lo..hi].iter() {
+            for v in self.triangles[idx].iter() {
+                min = min.inf(v);
+                max = max.sup(v);
+            }
+        }
+
+        let node = self.nodes.len();
+        self.nodes.push(BvhNode { min, max, kind: BvhKind::Leaf(lo, hi) });
+        if hi - lo <= BVH_LEAF_SIZE { return node; }
+
+        // Split at the median centroid along the longest axis of the box.
+        let axis = (max - min).imax();
+        let triangles = &self.triangles;
+        let centroid = |idx: &usize| triangles[*idx].iter().map(|v| v[axis]).sum::<f64>();
+        let mid = (hi - lo) / 2;
+        self.order[lo..hi].select_nth_unstable_by(mid, |a, b| centroid(a).total_cmp(&centroid(b)));
+
+        let left = self.build(lo, lo + mid);
+        let right = self.build(lo + mid, hi);
+        self.nodes[node].kind = BvhKind::Inner(left, right);
+        node
+    }
+}
+
+/// Squared distance from `pt` to the box spanned by `min` and `max` (zero inside).
+fn box_distance2(pt: &Vector3<f64>, min: &Vector3<f64>, max: &Vector3<f64>) -> f64 {
+    let mut d2 = 0.0;
+    for k in 0..3 {
+        let d = (min[k] - pt[k]).max(pt[k] - max[k]).max(0.0);
+        d2 += d * d;
+    }
+    d2
+}
+
+/// The point of triangle (a, b, c) closest to `p`, by the Voronoi region of `p` (see Ericson,
+/// Real-Time Collision Detection, 5.1.5).
+pub fn closest_point_on_triangle(p: &Vector3<f64>, a: &Vector3<f64>, b: &Vector3<f64>, c: &Vector3<f64>) -> Vector3<f64> {
+    let ab = b - a;
+    let ac = c - a;
+
+    let ap = p - a;
+    let d1 = ab.dot(&ap);
+    let d2 = ac.dot(&ap);
+    if d1 <= 0.0 && d2 <= 0.0 { return *a; }
+
+    let bp = p - b;
+    let d3 = ab.dot(&bp);
+    let d4 = ac.dot(&bp);
+    if d3 >= 0.0 && d4 <= d3 { return *b; }
+
+    let vc = d1 * d4 - d3 * d2;
+    if vc <= 0.0 && d1 >= 0.0 && d3 <= 0.0 { return a + ab * (d1 / (d1 - d3)); }
+
+    let cp = p - c;
+    let d5 = ab.dot(&cp);
+    let d6 = ac.dot(&cp);
+    if d6 >= 0.0 && d5 <= d6 { return *c; }
+
+    let vb = d5 * d2 - d1 * d6;
+    if vb <= 0.0 && d2 >= 0.0 && d6 <= 0.0 { return a + ac * (d2 / (d2 - d6)); }
+
+    let va = d3 * d6 - d5 * d4;
+    if va <= 0.0 && d4 - d3 >= 0.0 && d5 - d6 >= 0.0 { return b + (c - b) * ((d4 - d3) / ((d4 - d3) + (d5 - d6))); }
+
+    // Inside the triangle. Degenerate triangles fall back on a vertex.
+    let denom = va + vb + vc;
+    if denom == 0.0 { return *a; }
+    a + ab * (vb / denom) + ac * (vc / denom)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Deterministic pseudo-random integers in `0..n`, so that ties happen often.
+    fn lcg(seed: &mut u64, n: u64) -> f64 {
+        *seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        ((*seed >> 33) % n) as f64
+    }
+
+    fn random_point(seed: &mut u64) -> Vector3<f64> {
+        Vector3::new(lcg(seed, 10), lcg(seed, 10), lcg(seed, 10))
+    }
+
+    #[test]
+    fn kdtree_matches_brute_force() {
+        let mut seed = 1;
+        let mut points: Vec<Vector3<f64>> = (0..200).map(|_| random_point(&mut seed)).collect();
+        points.push(points[3]);
+        let tree = KdTree::new(&points);
+
+        let mut queries: Vec<Vector3<f64>> = (0..300).map(|_| random_point(&mut seed) + Vector3::repeat(0.5)).collect();
+        queries.push(points[3]);
+        for q in queries.iter() {
+            let mut best = None;
+            for (idx, p) in points.iter().enumerate() {
+                let d2 = (q - p).norm_squared();
+                if is_better(idx, d2, best) { best = Some((idx, d2)); }
+            }
+            assert_eq!(tree.nearest(q), best);
+        }
+        assert_eq!(tree.nearest(&points[3]), Some((3, 0.0)));
+        assert_eq!(KdTree::new(&[]).nearest(&points[0]), None);
+    }
+
+    #[test]
+    fn bvh_matches_brute_force() {
+        let mut seed = 2;
+        let mut triangles: Vec<[Vector3<f64>; 3]> = (0..100)
+            .map(|_| [random_point(&mut seed), random_point(&mut seed), random_point(&mut seed)])
+            .collect();
+        triangles.push(triangles[5]);
+        let bvh = Bvh::new(triangles.clone());
+
+        for _ in 0..300 {
+            let q = random_point(&mut seed) + Vector3::repeat(0.5);
+            let mut best: Option<(usize, f64)> = None;
+            for (idx, [a, b, c]) in triangles.iter().enumerate() {
+                let d2 = (q - closest_point_on_triangle(&q, a, b, c)).norm_squared();
+                if is_better(idx, d2, best) { best = Some((idx, d2)); }
+            }
+            assert_eq!(bvh.nearest(&q).map(|(_, idx, d2)| (idx, d2)), best);
+        }
+
+        let [a, b, c] = triangles[5];
+        let centroid = (a + b + c) / 3.0;
+        assert_eq!(bvh.nearest(&centroid).map(|(_, idx, _)| idx), Some(5));
+        assert!(Bvh::new(Vec::new()).nearest(&centroid).is_none());
+    }
+
+    #[test]
+    fn closest_point_regions() {
+        let a = Vector3::new(0.0, 0.0, 0.0);
+        let b = Vector3::new(1.0, 0.0, 0.0);
+        let c = Vector3::new(0.0, 1.0, 0.0);
+        let closest = |x: f64, y: f64, z: f64| closest_point_on_triangle(&Vector3::new(x, y, z), &a, &b, &c);
+
+        // Vertex regions.
+        assert_eq!(closest(-1.0, -1.0, 1.0), a);
+        assert_eq!(closest(2.0, -0.5, 0.0), b);
+        assert_eq!(closest(-1.0, 2.0, -3.0), c);
+        // Edge regions.
+        assert_eq!(closest(0.5, -1.0, 2.0), Vector3::new(0.5, 0.0, 0.0));
+        assert_eq!(closest(-1.0, 0.25, 0.0), Vector3::new(0.0, 0.25, 0.0));
+        assert_eq!(closest(1.0, 1.0, -1.0), Vector3::new(0.5, 0.5, 0.0));
+        // Interior.
+        assert_eq!(closest(0.25, 0.5, 5.0), Vector3::new(0.25, 0.5, 0.0));
+    }
+}
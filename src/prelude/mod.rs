@@ -1,12 +1,30 @@
 mod mesh;
 mod chain;
 mod current;
+mod homology;
 mod msp;
+mod msh;
+mod obj;
+mod off;
+mod ply;
+mod quality;
+#[cfg(feature = "serde")]
+mod serialize;
+mod sparse;
+mod subdivide;
+mod svg;
+mod tetgen;
+mod validate;
+mod vtk;
 
 pub use mesh::*;
 pub use chain::*;
 pub use current::*;
 pub use msp::*;
+pub use obj::*;
+pub use ply::*;
+pub use sparse::*;
+pub use svg::*;
 
 use std::rc::Rc;
 
@@ -0,0 +1,217 @@
+use super::{Mesh, Tetrahedron, Triangle};
+use nalgebra::Vector3;
+use std::collections::HashMap;
+use std::path::Path;
+
+const GMSH_TRIANGLE: usize = 2;
+const GMSH_TETRAHEDRON: usize = 4;
+
+/// Walks the whitespace separated tokens of a file, keeping track of line numbers.
+struct Tokens<'a> {
+    tokens: Vec<(usize, &'a str)>,
+    pos: usize,
+}
+
+impl<'a> Tokens<'a> {
+    fn new(text: &'a str) -> Self {
+        let tokens = text.lines().enumerate()
+            .flat_map(|(lineno, line)| line.split_whitespace().map(move |t| (lineno+1, t)))
+            .collect();
+        Tokens { tokens, pos: 0 }
+    }
+
+    fn next(&mut self) -> Result<(usize, &'a str), String> {
+        let token = *self.tokens.get(self.pos).ok_or("unexpected end of file".to_string())?;
+        self.pos += 1;
+        Ok(token)
+    }
+
+    fn parse<T: std::str::FromStr>(&mut self, what: &str) -> Result<T, String> {
+        let (lineno, s) = self.next()?;
+        s.parse::<T>().map_err(|_| format!("line {}: invalid {} '{}'", lineno, what, s))
+    }
+
+    /// Line number of the next token.
+    fn line(&self) -> usize {
+        self.tokens.get(self.pos).or(self.tokens.last()).map(|t| t.0).unwrap_or(0)
+    }
+
+    fn expect(&mut self, what: &str) -> Result<(), String> {
+        let (lineno, s) = self.next()?;
+        if s != what { return Err(format!("line {}: expected '{}', found '{}'", lineno, what, s)); }
+        Ok(())
+    }
+}
+
+impl Mesh {
+    /// Loads an ASCII Gmsh mesh (format 2.2 or 4.1). Tetrahedra make a volume mesh; without any,
+    /// the triangles make a surface mesh. Other elements are ignored.
+    pub fn load_msh<P: AsRef<Path>>(path: P) -> Result<Mesh, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("error opening file: {}", e))?;
+        let mut tokens = Tokens::new(&text);
+
+        let mut version = None;
+        let mut vertices = Vec::new();
+        let mut tags: HashMap<usize, usize> = HashMap::new();
+        let mut triangles = Vec::new();
+        let mut tetrahedra = Vec::new();
+
+        while tokens.pos < tokens.tokens.len() {
+            let (lineno, section) = tokens.next()?;
+            match section {
+                "$MeshFormat" => {
+                    let v: f64 = tokens.parse("version")?;
+                    let file_type: usize = tokens.parse("file type")?;
+                    let _data_size: usize = tokens.parse("data size")?;
+                    if file_type != 0 { return Err("binary Gmsh files are not supported".to_string()); }
+                    if v.floor() != 2.0 && v.floor() != 4.0 { return Err(format!("unsupported Gmsh version {}", v)); }
+                    version = Some(v.floor() as u32);
+                    tokens.expect("$EndMeshFormat")?;
+                }
+                "$Nodes" => {
+                    let version = version.ok_or(format!("line {}: $Nodes before $MeshFormat", lineno))?;
+                    let mut add_node = |tag: usize, x: f64, y: f64, z: f64| {
+                        tags.insert(tag, vertices.len());
+                        vertices.push(Vector3::new(x, y, z));
+                    };
+
+                    if version == 2 {
+                        let n: usize = tokens.parse("number of nodes")?;
+                        for _ in 0..n {
+                            let tag = tokens.parse("node tag")?;
+                            add_node(tag, tokens.parse("'x' coordinate")?, tokens.parse("'y' coordinate")?, tokens.parse("'z' coordinate")?);
+                        }
+                    } else {
+                        let blocks: usize = tokens.parse("number of blocks")?;
+                        let _n: usize = tokens.parse("number of nodes")?;
+                        let _min: usize = tokens.parse("minimum tag")?;
+                        let _max: usize = tokens.parse("maximum tag")?;
+                        for _ in 0..blocks {
+                            let _dim: usize = tokens.parse("entity dimension")?;
+                            let _entity: i64 = tokens.parse("entity tag")?;
+                            let parametric: usize = tokens.parse("parametric flag")?;
+                            if parametric != 0 { return Err("parametric Gmsh nodes are not supported".to_string()); }
+                            let n: usize = tokens.parse("number of nodes")?;
+
+                            let mut block_tags = Vec::with_capacity(n);
+                            for _ in 0..n { block_tags.push(tokens.parse::<usize>("node tag")?); }
+                            for tag in block_tags {
+                                add_node(tag, tokens.parse("'x' coordinate")?, tokens.parse("'y' coordinate")?, tokens.parse("'z' coordinate")?);
+                            }
+                        }
+                    }
+                    tokens.expect("$EndNodes")?;
+                }
+                "$Elements" => {
+                    let version = version.ok_or(format!("line {}: $Elements before $MeshFormat", lineno))?;
+                    let node = |tokens: &mut Tokens| -> Result<usize, String> {
+                        let (lineno, s) = tokens.next()?;
+                        let tag = s.parse::<usize>().map_err(|_| format!("line {}: invalid node tag '{}'", lineno, s))?;
+                        tags.get(&tag).copied().ok_or(format!("line {}: unknown node {}", lineno, tag))
+                    };
+                    let num_nodes = |ty: usize, lineno: usize| -> Result<usize, String> {
+                        match ty {
+                            1 => Ok(2), 2 => Ok(3), 3 => Ok(4), 4 => Ok(4), 5 => Ok(8), 6 => Ok(6), 7 => Ok(5),
+                            8 => Ok(3), 9 => Ok(6), 10 => Ok(9), 11 => Ok(10), 15 => Ok(1),
+                            _ => Err(format!("line {}: unsupported element type {}", lineno, ty)),
+                        }
+                    };
+
+                    let mut elements = Vec::new();
+                    if version == 2 {
+                        let n: usize = tokens.parse("number of elements")?;
+                        for _ in 0..n {
+                            let _tag: usize = tokens.parse("element tag")?;
+                            let lineno = tokens.line();
+                            let ty: usize = tokens.parse("element type")?;
+                            let num_tags: usize = tokens.parse("number of tags")?;
+                            for _ in 0..num_tags { tokens.next()?; }
+
+                            let mut nodes = Vec::new();
+                            for _ in 0..num_nodes(ty, lineno)? { nodes.push(node(&mut tokens)?); }
+                            elements.push((ty, nodes));
+                        }
+                    } else {
+                        let blocks: usize = tokens.parse("number of blocks")?;
+                        let _n: usize = tokens.parse("number of elements")?;
+                        let _min: usize = tokens.parse("minimum tag")?;
+                        let _max: usize = tokens.parse("maximum tag")?;
+                        for _ in 0..blocks {
+                            let _dim: usize = tokens.parse("entity dimension")?;
+                            let _entity: i64 = tokens.parse("entity tag")?;
+                            let lineno = tokens.line();
+                            let ty: usize = tokens.parse("element type")?;
+                            let n: usize = tokens.parse("number of elements")?;
+                            let k = num_nodes(ty, lineno)?;
+                            for _ in 0..n {
+                                let _tag: usize = tokens.parse("element tag")?;
+                                let mut nodes = Vec::new();
+                                for _ in 0..k { nodes.push(node(&mut tokens)?); }
+                                elements.push((ty, nodes));
+                            }
+                        }
+                    }
+
+                    for (ty, v) in elements {
+                        match ty {
+                            GMSH_TRIANGLE => triangles.push(Triangle(v[0], v[1], v[2])),
+                            GMSH_TETRAHEDRON => tetrahedra.push(Tetrahedron(v[0], v[1], v[2], v[3])),
+                            _ => {}
+                        }
+                    }
+                    tokens.expect("$EndElements")?;
+                }
+                _ => {
+                    // Skip any other section.
+                    if !section.starts_with('$') { return Err(format!("line {}: unexpected '{}'", lineno, section)); }
+                    let end = format!("$End{}", &section[1..]);
+                    while tokens.next()?.1 != end {}
+                }
+            }
+        }
+
+        if !tetrahedra.is_empty() { Ok(Mesh::from_tetrahedra(vertices, tetrahedra)) }
+        else if !triangles.is_empty() { Ok(Mesh::new(vertices, triangles)) }
+        else { Err("no triangles or tetrahedra found".to_string()) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn load_str(name: &str, contents: &str) -> Result<Mesh, String> {
+        let path = std::env::temp_dir().join(format!("msp-msh-{}-{}", std::process::id(), name));
+        std::fs::write(&path, contents).unwrap();
+        let mesh = Mesh::load_msh(&path);
+        std::fs::remove_file(&path).unwrap();
+        mesh
+    }
+
+    #[test]
+    fn version_2_volume() {
+        let mesh = load_str("v2.msh", "$MeshFormat\n2.2 0 8\n$EndMeshFormat\n\
+            $PhysicalNames\n1\n3 1 \"volume\"\n$EndPhysicalNames\n\
+            $Nodes\n5\n10 0 0 0\n20 1 0 0\n30 0 1 0\n40 0 0 1\n50 0 0 -1\n$EndNodes\n\
+            $Elements\n3\n1 15 2 0 1 10\n2 4 2 1 1 10 20 30 40\n3 4 2 1 1 10 30 20 50\n$EndElements\n").unwrap();
+        assert_eq!(mesh.vertices.len(), 5);
+        assert_eq!(mesh.tetrahedra, vec![Tetrahedron(0, 1, 2, 3), Tetrahedron(0, 2, 1, 4)]);
+        assert_eq!(mesh.triangles.len(), 7);
+    }
+
+    #[test]
+    fn version_4_surface() {
+        let mesh = load_str("v4.msh", "$MeshFormat\n4.1 0 8\n$EndMeshFormat\n\
+            $Nodes\n1 4 1 4\n2 1 0 4\n1\n2\n3\n4\n0 0 0\n1 0 0\n1 1 0\n0 1 0\n$EndNodes\n\
+            $Elements\n2 3 1 3\n1 1 1 1\n1 1 2\n2 1 2 2\n2 1 2 3\n3 1 3 4\n$EndElements\n").unwrap();
+        assert!(mesh.tetrahedra.is_empty());
+        assert_eq!(mesh.triangles, vec![Triangle(0, 1, 2), Triangle(0, 2, 3)]);
+    }
+
+    #[test]
+    fn unknown_node() {
+        let err = load_str("bad.msh", "$MeshFormat\n2.2 0 8\n$EndMeshFormat\n\
+            $Nodes\n3\n1 0 0 0\n2 1 0 0\n3 0 1 0\n$EndNodes\n$Elements\n1\n1 2 0 1 2 4\n$EndElements\n").err();
+        assert_eq!(err, Some("line 12: unknown node 4".to_string()));
+    }
+}
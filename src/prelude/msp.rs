@@ -1,6 +1,5 @@
 use super::*;
 use std::rc::Rc;
-use nalgebra::DMatrix;
 use good_lp::{constraint, default_solver, variable, variables, Expression, Variable, SolverModel, Solution};
 
 const EPSILON: f64 = 1e-6;
@@ -8,6 +7,21 @@ const EPSILON: f64 = 1e-6;
 pub struct MSPResult {
     pub median: Chain,
     pub decomp: Vec<(Chain, Chain)>,
+    pub alpha: Vec<f64>,
+    pub mu: f64,
+    pub lambda: f64,
+}
+
+impl MSPResult {
+    /// Lists the median followed by each decomposition pair, named `median`, `r1`, `s1`, `r2`, ...
+    pub fn chains(&self) -> Vec<(String, &Chain)> {
+        let mut chains = vec![("median".to_string(), &self.median)];
+        for (h, decomp) in self.decomp.iter().enumerate() {
+            chains.push((format!("r{}", h+1), &decomp.0));
+            chains.push((format!("s{}", h+1), &decomp.1));
+        }
+        chains
+    }
 }
 
 #[derive(Default)]
@@ -25,12 +39,22 @@ pub fn median_shape(mesh: Rc<Mesh>, input: Vec<Rc<Chain>>, alpha: Vec<f64>, mu:
         return Err(format!("invalid input, got {} chains and {} weights.", input.len(), alpha.len()));
     }
 
+    // Medians of curves (1-chains) are decomposed with areas and medians of surfaces (2-chains)
+    // with volumes.
+    let dim = input.first().map(|c| c.dim).unwrap_or(1);
+    if input.iter().any(|c| c.dim != dim) {
+        return Err("invalid input, chains have different dimensions.".to_string());
+    }
+    if dim != 1 && dim != 2 {
+        return Err(format!("invalid input, cannot compute the median of {}-chains.", dim));
+    }
+
 
     ///////////////////////////
     // Construct the problem //
     ///////////////////////////
-    let m = mesh.edges.len();
-    let n = mesh.triangles.len();
+    let m = mesh.num_simplices(dim);
+    let n = mesh.num_simplices(dim + 1);
     let N = input.len();
     let mut problem = variables!();
 
@@ -60,8 +84,10 @@ pub fn median_shape(mesh: Rc<Mesh>, input: Vec<Rc<Chain>>, alpha: Vec<f64>, mu:
     //  - Objective Function
     // sum {h in 1..k} ( a[h]*(sum {i in 1..m} w[i]*(qip[h,i]+qim[h,i])) + Lambda*(sum {j in 1..n} v[j]*(rip[h,j]+rim[h,j]))) 
     //      + Mu*(sum {i in 1..m} w[i]*(tp[i]+tm[i]));
-    let w: Vec<f64> = mesh.edges.iter().map(|e| e.length(&mesh)).collect();
-    let v: Vec<f64> = mesh.triangles.iter().map(|t| t.area(&mesh)).collect();
+    let (w, v): (Vec<f64>, Vec<f64>) = match dim {
+        1 => (mesh.edges.iter().map(|e| e.length(&mesh)).collect(), mesh.triangles.iter().map(|t| t.area(&mesh)).collect()),
+        _ => (mesh.triangles.iter().map(|t| t.area(&mesh)).collect(), mesh.tetrahedra.iter().map(|t| t.volume(&mesh)).collect()),
+    };
 
     let mut objective: Expression = 0.into();
     for i in 0..m {
@@ -81,27 +107,15 @@ pub fn median_shape(mesh: Rc<Mesh>, input: Vec<Rc<Chain>>, alpha: Vec<f64>, mu:
     //  - Constraints
     // subject to FlatDecomp {h in 1..k, i in 1..m}: tp[i]-tm[i] - Ti[h,i] = qip[h,i]-qim[h,i] + sum {j in 1..n} B[i,j]*(rip[h,j]-rim[h,j]);
     let mut constraints = Vec::new();
-    let B = DMatrix::from_fn(m, n, |r, c| {
-        let edge = mesh.edges[r];
-        let tri = mesh.triangles[c];
-
-        if !tri.is_face(&edge) { 0.0 }
-        else {
-            let mut ec = edge.clone();
-            ec.induce_orientation(&tri);
-            if ec.orientation() == tri.orientation() { 1.0 } else { -1.0 }
-        }
-    });
+    let B = mesh.boundary_operator(dim + 1);
 
     for h in 0..N {
         for i in 0..m {
             let lhs = vars.t_plus[i] - vars.t_minus[i] - input[h].coeff[i];
             let mut rhs = vars.r_plus[h][i] - vars.r_minus[h][i];
 
-            for j in 0..n {
-                if B[(i,j)] != 0.0 {
-                    rhs += B[(i,j)] * (vars.s_plus[h][j] - vars.s_minus[h][j]);
-                }
+            for (j, b) in B.row(i) {
+                rhs += b * (vars.s_plus[h][j] - vars.s_minus[h][j]);
             }
 
             //constraints.push(constraint!(lhs.clone() - rhs.clone() <= EPSILON));
@@ -120,11 +134,14 @@ pub fn median_shape(mesh: Rc<Mesh>, input: Vec<Rc<Chain>>, alpha: Vec<f64>, mu:
         .map_err(|e| format!("{}", e))?;
 
     let mut res = MSPResult {
-        median: Chain::zero(1, mesh.clone()),
+        median: Chain::zero(dim, mesh.clone()),
         decomp: Vec::new(),
+        alpha,
+        mu,
+        lambda,
     };
 
-    for _ in 0..N { res.decomp.push((Chain::zero(1, mesh.clone()), Chain::zero(2, mesh.clone()))); }
+    for _ in 0..N { res.decomp.push((Chain::zero(dim, mesh.clone()), Chain::zero(dim + 1, mesh.clone()))); }
 
     for i in 0..m {
         let v = solution.value(vars.t_plus[i]) - solution.value(vars.t_minus[i]);
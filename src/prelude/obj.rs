@@ -0,0 +1,375 @@
+use super::{Chain, Current, Edge, Mesh, Triangle};
+use nalgebra::Vector3;
+use std::collections::HashMap;
+use std::path::Path;
+use std::rc::Rc;
+use std::fs::File;
+use std::io::{self, prelude::*, BufReader, BufWriter};
+
+/// A named object (`o`) of a Wavefront OBJ file. Indices refer to `Wavefront::vertices`.
+pub struct WavefrontObject {
+    pub name: String,
+    pub faces: Vec<Vec<usize>>,
+    pub lines: Vec<Vec<usize>>,
+}
+
+/// The geometry of a Wavefront OBJ file. Vertices are shared between objects, as in the file.
+pub struct Wavefront {
+    pub vertices: Vec<Vector3<f64>>,
+    pub objects: Vec<WavefrontObject>,
+}
+
+impl Wavefront {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Wavefront, String> {
+        let file = File::open(path).map_err(|e| format!("error opening file: {}", e))?;
+
+        let mut obj = Wavefront {
+            vertices: Vec::new(),
+            objects: Vec::new(),
+        };
+
+        let reader = BufReader::new(file);
+        for (lineno, line) in reader.lines().enumerate() {
+            let line = line.map_err(|e| format!("error reading file: {}", e))?;
+            let line = match line.find('#') {
+                Some(idx) => &line[..idx],
+                None => &line[..],
+            };
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            if parts.is_empty() { continue; }
+
+            match parts[0] {
+                "o" => {
+                    let name = parts[1..].join(" ");
+                    obj.objects.push(WavefrontObject { name, faces: Vec::new(), lines: Vec::new() });
+                }
+                "v" => {
+                    if parts.len() < 4 { return Err(format!("line {}: vertex needs 3 coordinates", lineno+1)); }
+                    let x = parts[1].parse::<f64>().map_err(|_| format!("line {}: invalid 'x' coordinate", lineno+1))?;
+                    let y = parts[2].parse::<f64>().map_err(|_| format!("line {}: invalid 'y' coordinate", lineno+1))?;
+                    let z = parts[3].parse::<f64>().map_err(|_| format!("line {}: invalid 'z' coordinate", lineno+1))?;
+                    obj.vertices.push(Vector3::new(x, y, z));
+                }
+                "f" | "l" => {
+                    let mut indices = Vec::new();
+                    for part in parts[1..].iter() {
+                        indices.push(obj.parse_index(part).map_err(|e| format!("line {}: {}", lineno+1, e))?);
+                    }
+
+                    // Geometry before the first `o` statement belongs to an unnamed object.
+                    if obj.objects.is_empty() {
+                        obj.objects.push(WavefrontObject { name: String::new(), faces: Vec::new(), lines: Vec::new() });
+                    }
+                    let current = obj.objects.last_mut().unwrap();
+
+                    if parts[0] == "f" {
+                        if indices.len() < 3 { return Err(format!("line {}: face needs at least 3 vertices", lineno+1)); }
+                        current.faces.push(indices);
+                    } else {
+                        if indices.len() < 2 { return Err(format!("line {}: line needs at least 2 vertices", lineno+1)); }
+                        current.lines.push(indices);
+                    }
+                }
+                _ => {} // Normals, texture coordinates, materials, groups, ...
+            }
+        }
+
+        Ok(obj)
+    }
+
+    /// Parses a `v`, `v/vt`, `v//vn` or `v/vt/vn` reference into a 0-based vertex index.
+    /// Negative indices are relative to the vertices read so far.
+    fn parse_index(&self, s: &str) -> Result<usize, String> {
+        let v = s.split('/').next().unwrap_or("");
+        let idx = v.parse::<i64>().map_err(|_| format!("invalid vertex index '{}'", s))?;
+
+        let n = self.vertices.len() as i64;
+        let idx = if idx > 0 { idx - 1 } else { n + idx };
+        if idx < 0 || idx >= n { return Err(format!("vertex index '{}' out of bounds", s)); }
+
+        Ok(idx as usize)
+    }
+
+    pub fn object(&self, name: &str) -> Option<&WavefrontObject> {
+        self.objects.iter().find(|o| o.name == name)
+    }
+
+    /// Builds a mesh out of the faces of the object `name`. Polygons are triangulated as fans and
+    /// only the vertices referenced by the faces are kept.
+    pub fn mesh(&self, name: &str) -> Result<Mesh, String> {
+        let obj = self.object(name).ok_or(format!("unknown object '{}'", name))?;
+        if obj.faces.is_empty() { return Err(format!("object '{}' has no faces", name)); }
+
+        let mut used: Vec<usize> = obj.faces.iter().flatten().copied().collect();
+        used.sort();
+        used.dedup();
+        let local: HashMap<usize, usize> = used.iter().enumerate().map(|(i, v)| (*v, i)).collect();
+
+        let vertices = used.iter().map(|v| self.vertices[*v]).collect();
+        let mut triangles = Vec::new();
+        for face in obj.faces.iter() {
+            for k in 1..face.len()-1 {
+                triangles.push(Triangle(local[&face[0]], local[&face[k]], local[&face[k+1]]));
+            }
+        }
+
+        Ok(Mesh::new(vertices, triangles))
+    }
+
+    /// Builds a mesh out of the first object that has faces.
+    pub fn surface(&self) -> Result<Mesh, String> {
+        match self.objects.iter().find(|o| !o.faces.is_empty()) {
+            Some(obj) => self.mesh(&obj.name),
+            None => Err("no object with faces".to_string()),
+        }
+    }
+
+    /// Snaps the line elements of the object `name` onto `mesh`, like `Current::from_points`. The
+    /// segments must join up into a single polyline, which is walked starting from one of its ends.
+    pub fn current(&self, mesh: Rc<Mesh>, name: &str) -> Result<Current, String> {
+        self.current_within(mesh, name, f64::INFINITY)
+    }
+
+    /// Like `current`, but fails if a point is farther than `max_distance` from the surface, e.g.
+    /// `Mesh::snap_distance`.
+    pub fn current_within(&self, mesh: Rc<Mesh>, name: &str, max_distance: f64) -> Result<Current, String> {
+        let obj = self.object(name).ok_or(format!("unknown object '{}'", name))?;
+        if obj.lines.is_empty() { return Err(format!("object '{}' has no lines", name)); }
+
+        // Build the adjacency of the polyline.
+        let mut nbhrs: HashMap<usize, Vec<usize>> = HashMap::new();
+        let mut order = Vec::new();
+        let mut num_segments = 0;
+        for line in obj.lines.iter() {
+            for seg in line.windows(2) {
+                for (a, b) in [(seg[0], seg[1]), (seg[1], seg[0])] {
+                    if !nbhrs.contains_key(&a) { order.push(a); }
+                    nbhrs.entry(a).or_default().push(b);
+                }
+                num_segments += 1;
+            }
+        }
+
+        if nbhrs.values().any(|n| n.len() > 2) {
+            return Err(format!("object '{}' is not a simple polyline", name));
+        }
+
+        // Start from an end of the polyline, or anywhere if it is closed.
+        let start = *order.iter().find(|v| nbhrs[*v].len() == 1).unwrap_or(&order[0]);
+        let mut points = vec![self.vertices[start]];
+        let mut prev = None;
+        let mut cur = start;
+        for step in 0..num_segments {
+            let next = match nbhrs[&cur].iter().copied().find(|v| Some(*v) != prev) {
+                Some(v) if step == 0 || cur != start => v,
+                _ => return Err(format!("object '{}' is not a single polyline", name)),
+            };
+            points.push(self.vertices[next]);
+            prev = Some(cur);
+            cur = next;
+        }
+
+        Current::from_points_within(mesh, &points, max_distance)
+    }
+
+    /// Snaps every object made only of line elements onto `mesh`.
+    pub fn currents(&self, mesh: Rc<Mesh>) -> Result<Vec<(String, Current)>, String> {
+        self.objects.iter()
+            .filter(|o| o.faces.is_empty() && !o.lines.is_empty())
+            .map(|o| Ok((o.name.clone(), self.current(mesh.clone(), &o.name)?)))
+            .collect()
+    }
+}
+
+impl Mesh {
+    /// Saves the mesh as a Wavefront OBJ file. The triangles go in a `surface` object and each
+    /// chain is written as its own object, see `Chain::save_obj`.
+    pub fn save_obj<P: AsRef<Path>>(&self, path: P, chains: &[(&str, &Chain)]) -> io::Result<()> {
+        let mut file = BufWriter::new(File::create(path)?);
+        write_vertices(&mut file, self)?;
+
+        writeln!(file, "o surface")?;
+        for t in self.triangles.iter() {
+            writeln!(file, "f {} {} {}", t.0+1, t.1+1, t.2+1)?;
+        }
+
+        for (name, chain) in chains.iter() {
+            write_chain(&mut file, name, chain)?;
+        }
+        file.flush()
+    }
+}
+
+impl Chain {
+    /// Saves the chain as an object `name` of a Wavefront OBJ file, using the same vertex numbering
+    /// as `Mesh::save_obj`. Simplices are grouped by the magnitude of their coefficient, in
+    /// groups named `<name>_<magnitude>`, and the sign is carried by their orientation: 1-chains
+    /// are written as `l` polylines and 2-chains as `f` faces.
+    pub fn save_obj<P: AsRef<Path>>(&self, path: P, name: &str) -> io::Result<()> {
+        let mut file = BufWriter::new(File::create(path)?);
+        write_vertices(&mut file, &self.mesh)?;
+        write_chain(&mut file, name, self)?;
+        file.flush()
+    }
+}
+
+fn write_vertices<W: Write>(file: &mut W, mesh: &Mesh) -> io::Result<()> {
+    for v in mesh.vertices.iter() {
+        writeln!(file, "v {} {} {}", v.x, v.y, v.z)?;
+    }
+    Ok(())
+}
+
+fn write_chain<W: Write>(file: &mut W, name: &str, chain: &Chain) -> io::Result<()> {
+    if chain.dim != 1 && chain.dim != 2 {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("cannot write a {}-chain as an OBJ object", chain.dim)));
+    }
+
+    // Group the simplices by coefficient magnitude, oriented by the sign of the coefficient
+    // relative to the simplex as stored in the mesh.
+    let mut groups: Vec<(f64, Vec<(usize, bool)>)> = Vec::new();
+    for i in 0..chain.coeff.len() {
+        let c = chain.coeff[i] * chain.stored_sign(i);
+        if c == 0.0 { continue; }
+
+        match groups.iter_mut().find(|(m, _)| *m == c.abs()) {
+            Some(group) => group.1.push((i, c < 0.0)),
+            None => groups.push((c.abs(), vec![(i, c < 0.0)])),
+        }
+    }
+
+    writeln!(file, "o {}", name)?;
+    for (magnitude, simplices) in groups.iter() {
+        writeln!(file, "g {}_{}", name, magnitude)?;
+        if chain.dim == 1 {
+            let edges: Vec<Edge> = simplices.iter().map(|(i, flip)| {
+                let mut e = chain.mesh.edges[*i];
+                if *flip { e.swap_orientation(); }
+                e
+            }).collect();
+
+            for path in polylines(&edges) {
+                let path: Vec<String> = path.iter().map(|v| (v+1).to_string()).collect();
+                writeln!(file, "l {}", path.join(" "))?;
+            }
+        } else {
+            for (i, flip) in simplices.iter() {
+                let mut t = chain.mesh.triangles[*i];
+                if *flip { t.swap_orientation(); }
+                writeln!(file, "f {} {} {}", t.0+1, t.1+1, t.2+1)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Joins oriented edges into as few polylines as a greedy walk finds, starting from vertices
+/// with more outgoing than incoming edges.
+fn polylines(edges: &[Edge]) -> Vec<Vec<usize>> {
+    let mut out: HashMap<usize, Vec<usize>> = HashMap::new();
+    let mut incoming: HashMap<usize, usize> = HashMap::new();
+    for e in edges.iter().rev() {
+        out.entry(e.0).or_default().push(e.1);
+        *incoming.entry(e.1).or_default() += 1;
+    }
+
+    // A walk only changes the balance of the vertices it starts and ends at, and it ends where
+    // no edge is left, so every vertex needs to be looked at once per pass.
+    let mut paths = Vec::new();
+    for e in edges.iter() {
+        while out[&e.0].len() > incoming.get(&e.0).copied().unwrap_or(0) {
+            paths.push(walk(e.0, &mut out, &mut incoming));
+        }
+    }
+    for e in edges.iter() {
+        while !out[&e.0].is_empty() { paths.push(walk(e.0, &mut out, &mut incoming)); }
+    }
+
+    paths
+}
+
+fn walk(start: usize, out: &mut HashMap<usize, Vec<usize>>, incoming: &mut HashMap<usize, usize>) -> Vec<usize> {
+    let mut path = vec![start];
+    let mut cur = start;
+    while let Some(next) = out.get_mut(&cur).and_then(|o| o.pop()) {
+        *incoming.get_mut(&next).unwrap() -= 1;
+        path.push(next);
+        cur = next;
+    }
+    path
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prelude::generate::{grid, Diagonal};
+
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("msp-obj-{}-{}", std::process::id(), name))
+    }
+
+    #[test]
+    fn faces_keep_orientation() {
+        let mesh = Rc::new(grid(2.0, 2.0, 3, 3, Diagonal::Alternating));
+        let mut chain = Chain::zero(2, mesh.clone());
+        for i in 0..chain.coeff.len() { chain.coeff[i] = if i % 3 == 0 { -1.0 } else { 1.0 }; }
+        assert!((0..chain.coeff.len()).any(|i| chain.stored_sign(i) < 0.0));
+
+        let path = temp_path("faces.obj");
+        chain.save_obj(&path, "chain").unwrap();
+        let obj = Wavefront::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let mut loaded = Chain::zero(2, mesh.clone());
+        for face in obj.object("chain").unwrap().faces.iter() {
+            let (idx, sign) = mesh.find_triangle(face[0], face[1], face[2]).unwrap();
+            loaded.coeff[idx] += sign * loaded.stored_sign(idx);
+        }
+        assert_eq!(loaded.coeff, chain.coeff);
+    }
+
+    #[test]
+    fn lines_round_trip() {
+        let mesh = Rc::new(grid(2.0, 2.0, 3, 3, Diagonal::Forward));
+        let current = Current { mesh: mesh.clone(), path: vec![0, 1, 2, 6, 10, 9] };
+        let chain = current.as_chain().unwrap();
+
+        let path = temp_path("lines.obj");
+        mesh.save_obj(&path, &[("path", &chain)]).unwrap();
+        let loaded = Current::load(mesh.clone(), &path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded.path, current.path);
+    }
+
+    #[test]
+    fn distance_limit_is_opt_in() {
+        let mesh = Rc::new(grid(2.0, 2.0, 2, 2, Diagonal::Forward));
+        let path = temp_path("offset.obj");
+        std::fs::write(&path, "v -1 0 -1\nv 0 3 0\nv 1 0 1\no path\nl 1 2 3\n").unwrap();
+        let obj = Wavefront::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(obj.current(mesh.clone(), "path").is_ok());
+        assert!(obj.current_within(mesh.clone(), "path", mesh.snap_distance()).is_err());
+        assert!(obj.current_within(mesh.clone(), "path", 4.0).is_ok());
+    }
+
+    #[test]
+    fn polylines_join_edges() {
+        let edges = [Edge(0, 1), Edge(1, 2), Edge(3, 0), Edge(2, 4)];
+        assert_eq!(polylines(&edges), vec![vec![3, 0, 1, 2, 4]]);
+
+        let cycle = [Edge(0, 1), Edge(1, 2), Edge(2, 0)];
+        assert_eq!(polylines(&cycle), vec![vec![0, 1, 2, 0]]);
+    }
+
+    #[test]
+    fn other_dimensions_are_rejected() {
+        let mesh = Rc::new(grid(1.0, 1.0, 1, 1, Diagonal::Forward));
+        let path = temp_path("vertices.obj");
+        let result = Chain::zero(0, mesh).save_obj(&path, "vertices");
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::InvalidInput);
+    }
+}
@@ -0,0 +1,221 @@
+use super::{Mesh, Triangle};
+use nalgebra::Vector3;
+use std::fmt;
+use std::path::Path;
+use std::fs::File;
+use std::io::{prelude::*, BufReader, BufWriter};
+
+/// An error found while parsing an OFF file, along with where it was found.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OffError {
+    /// 1-based line number, or 0 when the error is not tied to a line (e.g. I/O errors).
+    pub line: usize,
+    /// The offending line, with comments removed.
+    pub text: String,
+    pub message: String,
+}
+
+impl fmt::Display for OffError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.line == 0 { write!(f, "{}", self.message) }
+        else { write!(f, "line {}: {} (\"{}\")", self.line, self.message, self.text) }
+    }
+}
+
+/// A non-empty line of the file with its comment stripped.
+struct OffLine {
+    lineno: usize,
+    text: String,
+}
+
+impl OffLine {
+    fn error(&self, message: String) -> OffError {
+        OffError { line: self.lineno, text: self.text.clone(), message }
+    }
+
+    fn tokens(&self) -> Vec<&str> {
+        self.text.split_whitespace().collect()
+    }
+
+    /// Whether the line reads as a face: a count of at least 3 followed by that many indices.
+    fn is_face(&self) -> bool {
+        let parts = self.tokens();
+        match parts[0].parse::<usize>() {
+            Ok(n) if n >= 3 && parts.len() > n => parts[1..n+1].iter().all(|p| p.parse::<usize>().is_ok()),
+            _ => false,
+        }
+    }
+}
+
+impl Mesh {
+    /// Loads an OFF file. The header may carry the `ST`, `C` and `N` prefixes and may share its
+    /// line with the counts. Extra per-vertex and per-face data (normals, colours, texture
+    /// coordinates) is ignored and polygons are triangulated as fans. A wrong vertex count is
+    /// recovered from by taking the rows before the first face as the vertices; the face count
+    /// must be right.
+    pub fn load_off<P: AsRef<Path>>(path: P) -> Result<Mesh, OffError> {
+        let io_error = |message: String| OffError { line: 0, text: String::new(), message };
+        let file = File::open(path).map_err(|e| io_error(format!("error opening file: {}", e)))?;
+
+        let mut lines = Vec::new();
+        let reader = BufReader::new(file);
+        for (lineno, line) in reader.lines().enumerate() {
+            let line = line.map_err(|e| io_error(format!("error reading file: {}", e)))?;
+            let text = match line.find('#') {
+                Some(idx) => &line[..idx],
+                None => &line[..],
+            }.trim();
+            if text.is_empty() { continue; }
+            lines.push(OffLine { lineno: lineno+1, text: text.to_string() });
+        }
+        let mut lines = lines.iter();
+
+        // Header, optionally followed by the counts.
+        let header = lines.next().ok_or(io_error("not an OFF file: empty file".to_string()))?;
+        let tokens = header.tokens();
+        let keyword = tokens[0];
+        let prefix = keyword.strip_suffix("OFF").ok_or(header.error("not an OFF file: missing header".to_string()))?;
+        let prefix = prefix.strip_prefix("ST").unwrap_or(prefix);
+        let prefix = prefix.strip_prefix('C').unwrap_or(prefix);
+        let prefix = prefix.strip_prefix('N').unwrap_or(prefix);
+        if !prefix.is_empty() { return Err(header.error(format!("unsupported OFF variant '{}'", keyword))); }
+        if tokens.get(1) == Some(&"BINARY") { return Err(header.error("binary OFF files are not supported".to_string())); }
+
+        let (counts_line, counts) = if tokens.len() > 1 {
+            (header, tokens[1..].to_vec())
+        } else {
+            let line = lines.next().ok_or(header.error("missing vertex and face counts".to_string()))?;
+            (line, line.tokens())
+        };
+        if counts.len() < 2 || counts.len() > 3 {
+            return Err(counts_line.error("expected the vertex, face and edge counts".to_string()));
+        }
+        let num_vertices = counts[0].parse::<usize>().map_err(|_| counts_line.error(format!("invalid number of vertices '{}'", counts[0])))?;
+        let num_faces = counts[1].parse::<usize>().map_err(|_| counts_line.error(format!("invalid number of faces '{}'", counts[1])))?;
+
+        // Trust the vertex count when the faces start right after it. Otherwise the vertices are
+        // the rows before the first face.
+        let data: Vec<&OffLine> = lines.collect();
+        let trusted = data.get(num_vertices).map(|l| l.is_face()).unwrap_or(num_faces == 0)
+            && (num_vertices == 0 || !data[num_vertices-1].is_face());
+        let num_vertices = if trusted { num_vertices } else { data.iter().position(|l| l.is_face()).unwrap_or(data.len()) };
+        let mut lines = data.into_iter();
+
+        // Vertices.
+        let mut vertices = Vec::with_capacity(num_vertices);
+        for i in 0..num_vertices {
+            let line = lines.next().ok_or(counts_line.error(format!("expected {} vertices, found {}", num_vertices, i)))?;
+            let parts = line.tokens();
+            if parts.len() < 3 { return Err(line.error(format!("vertex {} needs 3 coordinates", i))); }
+
+            let x = parts[0].parse::<f64>().map_err(|_| line.error(format!("invalid 'x' coordinate '{}'", parts[0])))?;
+            let y = parts[1].parse::<f64>().map_err(|_| line.error(format!("invalid 'y' coordinate '{}'", parts[1])))?;
+            let z = parts[2].parse::<f64>().map_err(|_| line.error(format!("invalid 'z' coordinate '{}'", parts[2])))?;
+            vertices.push(Vector3::new(x, y, z));
+        }
+
+        // Faces.
+        let mut triangles = Vec::with_capacity(num_faces);
+        for i in 0..num_faces {
+            let line = lines.next().ok_or(counts_line.error(format!("expected {} faces, found {}", num_faces, i)))?;
+            let parts = line.tokens();
+
+            let n = parts[0].parse::<usize>()
+                .map_err(|_| line.error(format!("invalid vertex count '{}' for face {}", parts[0], i)))?;
+            if n < 3 { return Err(line.error(format!("face {} has fewer than 3 vertices", i))); }
+            if parts.len() < n + 1 { return Err(line.error(format!("face {} lists fewer than {} vertices", i, n))); }
+
+            let mut face = Vec::with_capacity(n);
+            for part in parts[1..n+1].iter() {
+                let v = part.parse::<usize>().map_err(|_| line.error(format!("invalid vertex index '{}'", part)))?;
+                if v >= vertices.len() { return Err(line.error(format!("vertex index {} out of bounds", v))); }
+                face.push(v);
+            }
+
+            for k in 1..n-1 {
+                triangles.push(Triangle(face[0], face[k], face[k+1]));
+            }
+        }
+
+        if let Some(line) = lines.next() {
+            return Err(line.error(format!("unexpected data after {} vertices and {} faces", num_vertices, num_faces)));
+        }
+
+        Ok(Mesh::new(vertices, triangles))
+    }
+}
+
+impl Mesh {
+    pub fn save_off<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
+        let mut file = BufWriter::new(File::create(path)?);
+        writeln!(file, "OFF")?;
+        writeln!(file, "{} {} {}", self.vertices.len(), self.triangles.len(), self.edges.len())?;
+        for v in self.vertices.iter() {
+            writeln!(file, "{} {} {}", v.x, v.y, v.z)?;
+        }
+        for t in self.triangles.iter() {
+            writeln!(file, "3 {} {} {}", t.0, t.1, t.2)?;
+        }
+        file.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prelude::generate::{icosphere, torus};
+
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("msp-off-{}-{}", std::process::id(), name))
+    }
+
+    fn load_str(name: &str, contents: &str) -> Result<Mesh, OffError> {
+        let path = temp_path(name);
+        std::fs::write(&path, contents).unwrap();
+        let mesh = Mesh::load_off(&path);
+        std::fs::remove_file(&path).unwrap();
+        mesh
+    }
+
+    #[test]
+    fn round_trip() {
+        for mesh in [icosphere(1.0, 1), torus(2.0, 0.5, 6, 4)] {
+            let path = temp_path("round_trip.off");
+            mesh.save_off(&path).unwrap();
+            let loaded = Mesh::load_off(&path).unwrap();
+            std::fs::remove_file(&path).unwrap();
+
+            assert_eq!(loaded.vertices, mesh.vertices);
+            assert_eq!(loaded.triangles, mesh.triangles);
+            assert_eq!(loaded.edges.len(), mesh.edges.len());
+        }
+    }
+
+    #[test]
+    fn header_variants_and_polygons() {
+        let mesh = load_str("variants.off", "# a square\nCOFF 4 1 0\n0 0 0 1 0 0 1\n1 0 0 1 0 0 1\n1 1 0 1 0 0 1\n0 1 0 1 0 0 1\n4 0 1 2 3 255 0 0\n").unwrap();
+        assert_eq!(mesh.vertices.len(), 4);
+        assert_eq!(mesh.triangles, vec![Triangle(0, 1, 2), Triangle(0, 2, 3)]);
+    }
+
+    #[test]
+    fn wrong_vertex_count() {
+        let faces = "3 0 1 2\n3 0 2 3\n";
+        let vertices = "0 0 0\n1 0 0\n1 1 0\n0 1 0\n";
+        for declared in [2, 3, 5, 7] {
+            let mesh = load_str("wrong_count.off", &format!("OFF\n{} 2 0\n{}{}", declared, vertices, faces)).unwrap();
+            assert_eq!(mesh.vertices.len(), 4);
+            assert_eq!(mesh.triangles, vec![Triangle(0, 1, 2), Triangle(0, 2, 3)]);
+        }
+    }
+
+    #[test]
+    fn errors_carry_the_line() {
+        let err = load_str("bad_index.off", "OFF\n3 1 0\n0 0 0\n1 0 0\n0 1 0\n3 0 1 3\n").err().unwrap();
+        assert_eq!(err.line, 6);
+        assert_eq!(err.text, "3 0 1 3");
+
+        let err = load_str("missing_face.off", "OFF\n3 2 0\n0 0 0\n1 0 0\n0 1 0\n3 0 1 2\n").err().unwrap();
+        assert_eq!(err.line, 2);
+    }
+}
@@ -0,0 +1,475 @@
+use super::{Chain, Edge, Mesh, Triangle};
+use nalgebra::Vector3;
+use std::path::Path;
+use std::rc::Rc;
+use std::fs::File;
+use std::io::{self, prelude::*, BufWriter};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlyFormat {
+    Ascii,
+    BinaryLittleEndian,
+    BinaryBigEndian,
+}
+
+/// The element a PLY property is attached to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlyElement {
+    Vertex,
+    Edge,
+    Face,
+}
+
+/// A named scalar property with one value per vertex, edge or triangle of a mesh.
+#[derive(Debug, Clone)]
+pub struct PlyProperty {
+    pub element: PlyElement,
+    pub name: String,
+    pub values: Vec<f64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ScalarType {
+    I8, U8, I16, U16, I32, U32, F32, F64,
+}
+
+#[derive(Debug, Clone)]
+enum PropertyType {
+    Scalar(ScalarType),
+    List(ScalarType, ScalarType),
+}
+
+struct ElementDef {
+    name: String,
+    count: usize,
+    properties: Vec<(String, PropertyType)>,
+}
+
+impl ScalarType {
+    fn parse(s: &str) -> Result<ScalarType, String> {
+        match s {
+            "char" | "int8" => Ok(ScalarType::I8),
+            "uchar" | "uint8" => Ok(ScalarType::U8),
+            "short" | "int16" => Ok(ScalarType::I16),
+            "ushort" | "uint16" => Ok(ScalarType::U16),
+            "int" | "int32" => Ok(ScalarType::I32),
+            "uint" | "uint32" => Ok(ScalarType::U32),
+            "float" | "float32" => Ok(ScalarType::F32),
+            "double" | "float64" => Ok(ScalarType::F64),
+            _ => Err(format!("unknown property type '{}'", s)),
+        }
+    }
+
+    fn size(&self) -> usize {
+        match self {
+            ScalarType::I8 | ScalarType::U8 => 1,
+            ScalarType::I16 | ScalarType::U16 => 2,
+            ScalarType::I32 | ScalarType::U32 | ScalarType::F32 => 4,
+            ScalarType::F64 => 8,
+        }
+    }
+}
+
+impl PlyProperty {
+    /// Stores the coefficients of `chain` on the edges (1-chains) or faces (2-chains), relative
+    /// to the orientation of each edge or face as stored in the mesh.
+    pub fn from_chain(name: &str, chain: &Chain) -> Result<PlyProperty, String> {
+        let element = match chain.dim {
+            1 => PlyElement::Edge,
+            2 => PlyElement::Face,
+            _ => return Err(format!("cannot store a {}-chain as a PLY property", chain.dim)),
+        };
+
+        Ok(PlyProperty {
+            element,
+            name: name.to_string(),
+            values: (0..chain.coeff.len()).map(|i| chain.coeff[i] * chain.stored_sign(i)).collect(),
+        })
+    }
+
+    /// The chain stored by `from_chain`, on the mesh the property was loaded with.
+    pub fn to_chain(&self, mesh: Rc<Mesh>) -> Result<Chain, String> {
+        let dim = match self.element {
+            PlyElement::Vertex => return Err(format!("property '{}' is not on edges or faces", self.name)),
+            PlyElement::Edge => 1,
+            PlyElement::Face => 2,
+        };
+
+        let mut chain = Chain::zero(dim, mesh);
+        if self.values.len() != chain.coeff.len() {
+            return Err(format!("property '{}' has {} values, expected {}", self.name, self.values.len(), chain.coeff.len()));
+        }
+        for i in 0..self.values.len() { chain.coeff[i] = self.values[i] * chain.stored_sign(i); }
+        Ok(chain)
+    }
+}
+
+/// Reads the body of a PLY file one scalar at a time.
+struct Body<'a> {
+    format: PlyFormat,
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Body<'a> {
+    fn read(&mut self, ty: ScalarType) -> Result<f64, String> {
+        if self.format == PlyFormat::Ascii {
+            // Skip whitespace, then read a token.
+            while self.pos < self.data.len() && self.data[self.pos].is_ascii_whitespace() { self.pos += 1; }
+            let start = self.pos;
+            while self.pos < self.data.len() && !self.data[self.pos].is_ascii_whitespace() { self.pos += 1; }
+            if start == self.pos { return Err("unexpected end of file".to_string()); }
+
+            let token = std::str::from_utf8(&self.data[start..self.pos]).map_err(|_| "invalid token".to_string())?;
+            return token.parse::<f64>().map_err(|_| format!("invalid value '{}'", token));
+        }
+
+        let n = ty.size();
+        if self.pos + n > self.data.len() { return Err("unexpected end of file".to_string()); }
+        let mut bytes = [0u8; 8];
+        bytes[..n].copy_from_slice(&self.data[self.pos..self.pos+n]);
+        self.pos += n;
+        if self.format == PlyFormat::BinaryBigEndian { bytes[..n].reverse(); }
+
+        Ok(match ty {
+            ScalarType::I8 => i8::from_le_bytes([bytes[0]]) as f64,
+            ScalarType::U8 => bytes[0] as f64,
+            ScalarType::I16 => i16::from_le_bytes([bytes[0], bytes[1]]) as f64,
+            ScalarType::U16 => u16::from_le_bytes([bytes[0], bytes[1]]) as f64,
+            ScalarType::I32 => i32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as f64,
+            ScalarType::U32 => u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as f64,
+            ScalarType::F32 => f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as f64,
+            ScalarType::F64 => f64::from_le_bytes(bytes),
+        })
+    }
+
+    fn read_index(&mut self, ty: ScalarType) -> Result<usize, String> {
+        let v = self.read(ty)?;
+        if v < 0.0 || v.fract() != 0.0 { return Err(format!("invalid index {}", v)); }
+        Ok(v as usize)
+    }
+}
+
+impl Mesh {
+    /// Loads a PLY mesh (ASCII or binary) along with its scalar vertex, edge and face properties.
+    /// Polygons are triangulated as fans and each triangle inherits its polygon's properties.
+    pub fn load_ply<P: AsRef<Path>>(path: P) -> Result<(Mesh, Vec<PlyProperty>), String> {
+        let data = std::fs::read(path).map_err(|e| format!("error opening file: {}", e))?;
+
+        // Parse the header.
+        let mut format = None;
+        let mut elements: Vec<ElementDef> = Vec::new();
+        let mut pos = 0;
+        let mut lineno = 0;
+        loop {
+            let end = match data[pos..].iter().position(|c| *c == b'\n') {
+                Some(idx) => pos + idx,
+                None => return Err("invalid PLY file: missing 'end_header'".to_string()),
+            };
+            let line = String::from_utf8_lossy(&data[pos..end]).trim().to_string();
+            pos = end + 1;
+            lineno += 1;
+
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            if lineno == 1 {
+                if line != "ply" { return Err("not a PLY file: missing header".to_string()); }
+                continue;
+            }
+            if parts.is_empty() { continue; }
+
+            match parts[0] {
+                "format" => {
+                    format = Some(match parts.get(1) {
+                        Some(&"ascii") => PlyFormat::Ascii,
+                        Some(&"binary_little_endian") => PlyFormat::BinaryLittleEndian,
+                        Some(&"binary_big_endian") => PlyFormat::BinaryBigEndian,
+                        _ => return Err(format!("line {}: unknown format '{}'", lineno, line)),
+                    });
+                }
+                "element" => {
+                    if parts.len() != 3 { return Err(format!("line {}: invalid element '{}'", lineno, line)); }
+                    let count = parts[2].parse::<usize>().map_err(|_| format!("line {}: invalid element count", lineno))?;
+                    elements.push(ElementDef { name: parts[1].to_string(), count, properties: Vec::new() });
+                }
+                "property" => {
+                    let elem = elements.last_mut().ok_or(format!("line {}: property outside of an element", lineno))?;
+                    let prop = if parts.get(1) == Some(&"list") {
+                        if parts.len() != 5 { return Err(format!("line {}: invalid property '{}'", lineno, line)); }
+                        let count = ScalarType::parse(parts[2]).map_err(|e| format!("line {}: {}", lineno, e))?;
+                        let item = ScalarType::parse(parts[3]).map_err(|e| format!("line {}: {}", lineno, e))?;
+                        (parts[4].to_string(), PropertyType::List(count, item))
+                    } else {
+                        if parts.len() != 3 { return Err(format!("line {}: invalid property '{}'", lineno, line)); }
+                        let ty = ScalarType::parse(parts[1]).map_err(|e| format!("line {}: {}", lineno, e))?;
+                        (parts[2].to_string(), PropertyType::Scalar(ty))
+                    };
+                    elem.properties.push(prop);
+                }
+                "end_header" => break,
+                _ => {} // comment, obj_info, ...
+            }
+        }
+
+        let mut body = Body {
+            format: format.ok_or("invalid PLY file: missing format".to_string())?,
+            data: &data[pos..],
+            pos: 0,
+        };
+
+        // Parse the body.
+        let mut vertices = Vec::new();
+        let mut faces: Vec<Vec<usize>> = Vec::new();
+        let mut edges = Vec::new();
+        let mut vertex_props: Vec<(String, Vec<f64>)> = Vec::new();
+        let mut face_props: Vec<(String, Vec<f64>)> = Vec::new();
+        let mut edge_props: Vec<(String, Vec<f64>)> = Vec::new();
+
+        let mut other_props = Vec::new();
+
+        for elem in elements.iter() {
+            let props = match elem.name.as_str() {
+                "vertex" => &mut vertex_props,
+                "face" => &mut face_props,
+                "edge" => &mut edge_props,
+                _ => &mut other_props,
+            };
+
+            let skip = ["x", "y", "z", "vertex1", "vertex2"];
+            let scalars: Vec<&String> = elem.properties.iter()
+                .filter(|(name, ty)| matches!(ty, PropertyType::Scalar(_)) && !skip.contains(&name.as_str()))
+                .map(|(name, _)| name)
+                .collect();
+            props.extend(scalars.iter().map(|name| (name.to_string(), Vec::new())));
+
+            for i in 0..elem.count {
+                let mut pt = Vector3::zeros();
+                let mut face = Vec::new();
+                let mut edge = Edge(0, 0);
+                let mut values = Vec::new();
+
+                for (name, ty) in elem.properties.iter() {
+                    match ty {
+                        PropertyType::Scalar(ty) => {
+                            let v = body.read(*ty).map_err(|e| format!("{} {}: {}", elem.name, i, e))?;
+                            match name.as_str() {
+                                "x" => pt.x = v,
+                                "y" => pt.y = v,
+                                "z" => pt.z = v,
+                                "vertex1" => edge.0 = v as usize,
+                                "vertex2" => edge.1 = v as usize,
+                                _ => values.push(v),
+                            }
+                        }
+                        PropertyType::List(count, item) => {
+                            let n = body.read_index(*count).map_err(|e| format!("{} {}: {}", elem.name, i, e))?;
+                            let mut list = Vec::with_capacity(n);
+                            for _ in 0..n {
+                                list.push(body.read_index(*item).map_err(|e| format!("{} {}: {}", elem.name, i, e))?);
+                            }
+                            if name == "vertex_indices" || name == "vertex_index" { face = list; }
+                        }
+                    }
+                }
+
+                match elem.name.as_str() {
+                    "vertex" => vertices.push(pt),
+                    "face" => {
+                        if face.len() < 3 { return Err(format!("face {}: needs at least 3 vertices", i)); }
+                        faces.push(face);
+                    }
+                    "edge" => edges.push(edge),
+                    _ => {}
+                }
+
+                let offset = props.len() - scalars.len();
+                for (k, v) in values.into_iter().enumerate() {
+                    props[offset + k].1.push(v);
+                }
+            }
+        }
+
+        // Triangulate the faces.
+        let mut triangles = Vec::new();
+        let mut parent = Vec::new();
+        for (f, face) in faces.iter().enumerate() {
+            if face.iter().any(|v| *v >= vertices.len()) { return Err(format!("face {}: index out of bounds", f)); }
+            for k in 1..face.len()-1 {
+                triangles.push(Triangle(face[0], face[k], face[k+1]));
+                parent.push(f);
+            }
+        }
+
+        let mesh = Mesh::new(vertices, triangles);
+
+        let mut properties = Vec::new();
+        for (name, values) in vertex_props {
+            properties.push(PlyProperty { element: PlyElement::Vertex, name, values });
+        }
+        if !edge_props.is_empty() {
+            for (name, values) in edge_props {
+                let mut coeff = vec![0.0; mesh.edges.len()];
+                for (e, v) in edges.iter().zip(values) {
+                    let (idx, sign) = mesh.find_edge(e.0, e.1).ok_or(format!("unknown edge {:?}", (e.0, e.1)))?;
+                    coeff[idx] = sign * v;
+                }
+                properties.push(PlyProperty { element: PlyElement::Edge, name, values: coeff });
+            }
+        }
+
+        for (name, values) in face_props {
+            let values = parent.iter().map(|f| values[*f]).collect();
+            properties.push(PlyProperty { element: PlyElement::Face, name, values });
+        }
+        Ok((mesh, properties))
+    }
+
+    /// Saves the mesh as PLY. Every property is written as a `double` on its element; the edge
+    /// element is only written when there is at least one edge property.
+    pub fn save_ply<P: AsRef<Path>>(&self, path: P, format: PlyFormat, properties: &[PlyProperty]) -> io::Result<()> {
+        for prop in properties.iter() {
+            let n = match prop.element {
+                PlyElement::Vertex => self.vertices.len(),
+                PlyElement::Edge => self.edges.len(),
+                PlyElement::Face => self.triangles.len(),
+            };
+            if prop.values.len() != n {
+                return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("property '{}' has {} values, expected {}", prop.name, prop.values.len(), n)));
+            }
+        }
+        let props_of = |element| properties.iter().filter(move |p| p.element == element);
+        let num_edge_props = props_of(PlyElement::Edge).count();
+
+        let mut file = BufWriter::new(File::create(path)?);
+        writeln!(file, "ply")?;
+        match format {
+            PlyFormat::Ascii => writeln!(file, "format ascii 1.0")?,
+            PlyFormat::BinaryLittleEndian => writeln!(file, "format binary_little_endian 1.0")?,
+            PlyFormat::BinaryBigEndian => writeln!(file, "format binary_big_endian 1.0")?,
+        }
+        writeln!(file, "element vertex {}", self.vertices.len())?;
+        writeln!(file, "property double x\nproperty double y\nproperty double z")?;
+        for prop in props_of(PlyElement::Vertex) { writeln!(file, "property double {}", prop.name)?; }
+        if num_edge_props > 0 {
+            writeln!(file, "element edge {}", self.edges.len())?;
+            writeln!(file, "property int vertex1\nproperty int vertex2")?;
+            for prop in props_of(PlyElement::Edge) { writeln!(file, "property double {}", prop.name)?; }
+        }
+        writeln!(file, "element face {}", self.triangles.len())?;
+        writeln!(file, "property list uchar int vertex_indices")?;
+        for prop in props_of(PlyElement::Face) { writeln!(file, "property double {}", prop.name)?; }
+        writeln!(file, "end_header")?;
+
+        let mut row = Row { format, file: &mut file, first: true };
+        for i in 0..self.vertices.len() {
+            let v = self.vertices[i];
+            row.f64(v.x)?; row.f64(v.y)?; row.f64(v.z)?;
+            for prop in props_of(PlyElement::Vertex) { row.f64(prop.values[i])?; }
+            row.end()?;
+        }
+        if num_edge_props > 0 {
+            for i in 0..self.edges.len() {
+                row.i32(self.edges[i].0 as i32)?; row.i32(self.edges[i].1 as i32)?;
+                for prop in props_of(PlyElement::Edge) { row.f64(prop.values[i])?; }
+                row.end()?;
+            }
+        }
+        for i in 0..self.triangles.len() {
+            let t = self.triangles[i];
+            row.u8(3)?; row.i32(t.0 as i32)?; row.i32(t.1 as i32)?; row.i32(t.2 as i32)?;
+            for prop in props_of(PlyElement::Face) { row.f64(prop.values[i])?; }
+            row.end()?;
+        }
+
+        file.flush()
+    }
+}
+
+/// Writes the values of one element, either as a line of text or as packed binary.
+struct Row<'a, W: Write> {
+    format: PlyFormat,
+    file: &'a mut W,
+    first: bool,
+}
+
+impl<'a, W: Write> Row<'a, W> {
+    fn write(&mut self, text: String, le: &[u8], be: &[u8]) -> io::Result<()> {
+        match self.format {
+            PlyFormat::Ascii => {
+                if !self.first { write!(self.file, " ")?; }
+                self.first = false;
+                write!(self.file, "{}", text)
+            }
+            PlyFormat::BinaryLittleEndian => self.file.write_all(le),
+            PlyFormat::BinaryBigEndian => self.file.write_all(be),
+        }
+    }
+
+    fn u8(&mut self, v: u8) -> io::Result<()> { self.write(v.to_string(), &[v], &[v]) }
+    fn i32(&mut self, v: i32) -> io::Result<()> { self.write(v.to_string(), &v.to_le_bytes(), &v.to_be_bytes()) }
+    fn f64(&mut self, v: f64) -> io::Result<()> { self.write(v.to_string(), &v.to_le_bytes(), &v.to_be_bytes()) }
+
+    fn end(&mut self) -> io::Result<()> {
+        self.first = true;
+        if self.format == PlyFormat::Ascii { writeln!(self.file)?; }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prelude::generate::{grid, Diagonal};
+
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("msp-ply-{}-{}", std::process::id(), name))
+    }
+
+    #[test]
+    fn chains_round_trip() {
+        let mesh = Rc::new(grid(2.0, 2.0, 2, 2, Diagonal::Alternating));
+        let mut edges = Chain::zero(1, mesh.clone());
+        let mut faces = Chain::zero(2, mesh.clone());
+        for i in 0..edges.coeff.len() { edges.coeff[i] = i as f64 - 3.5; }
+        for i in 0..faces.coeff.len() { faces.coeff[i] = if i % 2 == 0 { 1.5 } else { -2.0 }; }
+        let properties = vec![
+            PlyProperty::from_chain("edges", &edges).unwrap(),
+            PlyProperty::from_chain("faces", &faces).unwrap(),
+        ];
+
+        for format in [PlyFormat::Ascii, PlyFormat::BinaryLittleEndian, PlyFormat::BinaryBigEndian] {
+            let path = temp_path(&format!("{:?}.ply", format));
+            mesh.save_ply(&path, format, &properties).unwrap();
+            let (loaded, props) = Mesh::load_ply(&path).unwrap();
+            std::fs::remove_file(&path).unwrap();
+
+            let loaded = Rc::new(loaded);
+            assert_eq!(loaded.vertices.len(), mesh.vertices.len());
+            assert_eq!(props.len(), 2);
+            assert_eq!(props[0].to_chain(loaded.clone()).unwrap().coeff, edges.coeff);
+            assert_eq!(props[1].to_chain(loaded.clone()).unwrap().coeff, faces.coeff);
+        }
+    }
+
+    #[test]
+    fn reversed_edge_keeps_sign() {
+        let path = temp_path("reversed.ply");
+        std::fs::write(&path, "ply\nformat ascii 1.0\n\
+            element vertex 3\nproperty double x\nproperty double y\nproperty double z\n\
+            element face 1\nproperty list uchar int vertex_indices\n\
+            element edge 1\nproperty int vertex1\nproperty int vertex2\nproperty double flow\n\
+            end_header\n0 0 0\n1 0 0\n0 1 0\n3 0 1 2\n1 0 5.0\n").unwrap();
+        let (mesh, props) = Mesh::load_ply(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let chain = props[0].to_chain(Rc::new(mesh)).unwrap();
+        let (idx, sign) = chain.mesh.find_edge(1, 0).unwrap();
+        assert_eq!(chain.coeff[idx], sign * 5.0);
+        assert_eq!(chain.coeff.iter().sum::<f64>(), -5.0);
+    }
+
+    #[test]
+    fn from_chain_rejects_other_dimensions() {
+        let mesh = Rc::new(grid(1.0, 1.0, 1, 1, Diagonal::Forward));
+        assert!(PlyProperty::from_chain("vertices", &Chain::zero(0, mesh)).is_err());
+    }
+}
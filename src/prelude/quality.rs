@@ -0,0 +1,391 @@
+use super::{Chain, Current, Edge, Mesh, Orientation, Triangle};
+use super::validate::DEGENERATE_AREA;
+use nalgebra::Vector3;
+use std::collections::{HashMap, VecDeque};
+use std::f64::consts::PI;
+use std::fmt;
+use std::rc::Rc;
+
+/// Edges are flipped when the angles opposite to them add up to more than π by this much.
+const FLIP_TOLERANCE: f64 = 1e-9;
+
+/// Shape statistics of the triangles and edges of a mesh, see `Mesh::quality`. Angles are in
+/// degrees. The aspect ratio of a triangle is its longest side over its inradius, scaled so that
+/// equilateral triangles have 1. Degenerate triangles (see `MeshReport::degenerate_triangles`)
+/// are only counted, and statistics over no triangles or edges are 0.
+#[derive(Debug, Clone, Default)]
+pub struct QualityStats {
+    pub min_angle: f64,
+    pub max_angle: f64,
+    /// Mean over the triangles of their smallest angle.
+    pub mean_min_angle: f64,
+    pub max_aspect_ratio: f64,
+    pub mean_aspect_ratio: f64,
+    pub min_edge_length: f64,
+    pub max_edge_length: f64,
+    pub mean_edge_length: f64,
+    /// Standard deviation of the edge lengths over their mean.
+    pub edge_length_spread: f64,
+    /// Interior edges whose opposite angles add up to more than π.
+    pub non_delaunay_edges: usize,
+    /// Triangles left out of the angle and aspect ratio statistics.
+    pub degenerate_triangles: usize,
+}
+
+impl fmt::Display for QualityStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "angles: min {:.2}, max {:.2}, mean min {:.2}", self.min_angle, self.max_angle, self.mean_min_angle)?;
+        writeln!(f, "aspect ratio: max {:.3}, mean {:.3}", self.max_aspect_ratio, self.mean_aspect_ratio)?;
+        writeln!(f, "edge lengths: min {}, max {}, mean {}, spread {:.3}", self.min_edge_length, self.max_edge_length, self.mean_edge_length, self.edge_length_spread)?;
+        writeln!(f, "non-Delaunay edges: {}", self.non_delaunay_edges)?;
+        write!(f, "degenerate triangles: {}", self.degenerate_triangles)
+    }
+}
+
+/// Angle at `c` in the triangle (a, b, c).
+fn angle(a: &Vector3<f64>, b: &Vector3<f64>, c: &Vector3<f64>) -> f64 {
+    (a - c).angle(&(b - c))
+}
+
+/// Coefficient sign of a triangle relative to its winding (see `Mesh::boundary_operator`).
+fn winding_sign(tri: &Triangle) -> f64 {
+    if tri.orientation() == Orientation::CCW { 1.0 } else { -1.0 }
+}
+
+/// A mesh with improved triangles along with how its simplices relate to the original one, to
+/// carry chains and currents over. See `Mesh::delaunay_flip` and `Mesh::smooth`.
+///
+/// The vertices keep their indices and the simplices their winding. Every edge and triangle
+/// keeps its index too, except that a flipped edge is replaced by the other diagonal of its two
+/// triangles, which are replaced by the two triangles on that diagonal.
+pub struct Remesh<'a> {
+    original: &'a Mesh,
+    pub mesh: Rc<Mesh>,
+    /// Whether each edge was flipped.
+    flipped: Vec<bool>,
+    /// The original triangles whose union each triangle lies on.
+    origins: Vec<Vec<usize>>,
+}
+
+impl<'a> Remesh<'a> {
+    /// Carries a 1- or 2-chain over to the new mesh. Fails if the chain does not survive the
+    /// flips, i.e. if it has a nonzero coefficient on a flipped edge or different coefficients
+    /// on two triangles that were flipped; chains passed to `Mesh::delaunay_flip` always survive.
+    pub fn transfer(&self, chain: &Chain) -> Result<Chain, String> {
+        if !std::ptr::eq(&*chain.mesh, self.original) { return Err("chain is defined on another mesh".to_string()); }
+
+        let mut res = Chain::zero(chain.dim, self.mesh.clone());
+        match chain.dim {
+            1 => for (e, flipped) in self.flipped.iter().enumerate() {
+                if !flipped { res.coeff[e] = chain.coeff[e]; }
+                else if chain.coeff[e] != 0.0 { return Err(format!("edge {:?} was flipped", self.original.edges[e])); }
+            },
+            2 => for (t, origins) in self.origins.iter().enumerate() {
+                // Coefficients relative to the winding, which all triangles share.
+                let value = chain.coeff[origins[0]] * winding_sign(&self.original.triangles[origins[0]]);
+                if origins.iter().any(|o| chain.coeff[*o] * winding_sign(&self.original.triangles[*o]) != value) {
+                    return Err(format!("triangles {:?} were flipped but have different coefficients", origins));
+                }
+                res.coeff[t] = value * winding_sign(&self.mesh.triangles[t]);
+            },
+            _ => return Err(format!("cannot transfer a {}-chain", chain.dim)),
+        }
+
+        Ok(res)
+    }
+
+    /// Carries a current over to the new mesh. Fails if it runs along a flipped edge.
+    pub fn transfer_current(&self, current: &Current) -> Result<Current, String> {
+        if !std::ptr::eq(&*current.mesh, self.original) { return Err("current is defined on another mesh".to_string()); }
+        for i in 1..current.path.len() {
+            let (a, b) = (current.path[i-1], current.path[i]);
+            if self.mesh.find_edge(a, b).is_none() { return Err(format!("edge {:?} was flipped", (a, b))); }
+        }
+
+        Ok(Current {
+            mesh: self.mesh.clone(),
+            path: current.path.clone(),
+        })
+    }
+}
+
+impl Mesh {
+    /// Shape statistics of the triangles and edges, see `QualityStats`.
+    pub fn quality(&self) -> QualityStats {
+        let mut stats = QualityStats { min_angle: f64::INFINITY, ..Default::default() };
+
+        for tri in self.triangles.iter() {
+            let (a, b, c) = (&self.vertices[tri.0], &self.vertices[tri.1], &self.vertices[tri.2]);
+            let sides = [(b - c).norm(), (c - a).norm(), (a - b).norm()];
+            let longest = sides.iter().copied().fold(0.0, f64::max);
+            let area = tri.area(self);
+            if area <= DEGENERATE_AREA * longest * longest {
+                stats.degenerate_triangles += 1;
+                continue;
+            }
+
+            let angles = [angle(b, c, a), angle(c, a, b), angle(a, b, c)].map(f64::to_degrees);
+            let smallest = angles.iter().copied().fold(f64::INFINITY, f64::min);
+            stats.min_angle = stats.min_angle.min(smallest);
+            stats.max_angle = stats.max_angle.max(angles.iter().copied().fold(0.0, f64::max));
+            stats.mean_min_angle += smallest;
+
+            let perimeter: f64 = sides.iter().sum();
+            let aspect = longest * perimeter / (4.0 * 3.0_f64.sqrt() * area);
+            stats.max_aspect_ratio = stats.max_aspect_ratio.max(aspect);
+            stats.mean_aspect_ratio += aspect;
+        }
+
+        let lengths: Vec<f64> = self.edges.iter().map(|e| e.length(self)).collect();
+        let mean = lengths.iter().sum::<f64>() / lengths.len().max(1) as f64;
+        let variance = lengths.iter().map(|l| (l - mean) * (l - mean)).sum::<f64>() / lengths.len().max(1) as f64;
+        stats.min_edge_length = lengths.iter().copied().fold(f64::INFINITY, f64::min);
+        stats.max_edge_length = lengths.iter().copied().fold(0.0, f64::max);
+        stats.mean_edge_length = mean;
+        stats.edge_length_spread = if mean > 0.0 { variance.sqrt() / mean } else { 0.0 };
+        if lengths.is_empty() { stats.min_edge_length = 0.0; }
+
+        let n = self.triangles.len() - stats.degenerate_triangles;
+        if n == 0 { stats.min_angle = 0.0; }
+        let n = n.max(1) as f64;
+        stats.mean_min_angle /= n;
+        stats.mean_aspect_ratio /= n;
+        stats.non_delaunay_edges = (0..self.edges.len())
+            .filter(|e| {
+                let faces = self.edge_triangles(*e);
+                faces.len() == 2 && self.opposite_angles(*e, faces[0], faces[1]) > PI + FLIP_TOLERANCE
+            })
+            .count();
+
+        stats
+    }
+
+    /// Sum of the angles opposite to edge `e` in triangles `t1` and `t2`.
+    fn opposite_angles(&self, e: usize, t1: usize, t2: usize) -> f64 {
+        let Edge(a, b) = self.edges[e];
+        let opposite = |t: usize| {
+            let Triangle(i, j, k) = self.triangles[t];
+            [i, j, k].into_iter().find(|v| *v != a && *v != b).unwrap()
+        };
+        let (pa, pb) = (&self.vertices[a], &self.vertices[b]);
+        angle(pa, pb, &self.vertices[opposite(t1)]) + angle(pa, pb, &self.vertices[opposite(t2)])
+    }
+
+    /// Flips edges until the mesh is Delaunay: the angles opposite to every interior edge add up
+    /// to at most π. Meant for planar and near-planar meshes; an edge is only flipped if its two
+    /// triangles are consistently oriented and the new triangles face the same way as the old
+    /// ones.
+    ///
+    /// The given chains are kept: edges carrying a 1-chain and edges between triangles with
+    /// different coefficients in a 2-chain are not flipped. Fails on volume meshes and on chains
+    /// defined on another mesh.
+    pub fn delaunay_flip(&self, chains: &[&Chain]) -> Result<Remesh<'_>, String> {
+        if !self.tetrahedra.is_empty() { return Err("only surface meshes can be flipped".to_string()); }
+
+        let mut edges = self.edges.clone();
+        let mut triangles = self.triangles.clone();
+        let mut edge_tris: Vec<Vec<usize>> = (0..edges.len()).map(|e| self.edge_triangles(e).to_vec()).collect();
+        let mut lookup: HashMap<(usize, usize), usize> = edges.iter().enumerate().map(|(i, e)| ((e.0.min(e.1), e.0.max(e.1)), i)).collect();
+        let mut flipped = vec![false; edges.len()];
+        let mut origins: Vec<Vec<usize>> = (0..triangles.len()).map(|t| vec![t]).collect();
+
+        let mut locked = vec![false; edges.len()];
+        for chain in chains.iter() {
+            if !std::ptr::eq(&*chain.mesh, self) { return Err("chain is defined on another mesh".to_string()); }
+            match chain.dim {
+                1 => for (e, c) in chain.coeff.iter().enumerate() { if *c != 0.0 { locked[e] = true; } },
+                2 => for (e, faces) in edge_tris.iter().enumerate() {
+                    let values: Vec<f64> = faces.iter().map(|t| chain.coeff[*t] * winding_sign(&triangles[*t])).collect();
+                    if values.windows(2).any(|w| w[0] != w[1]) { locked[e] = true; }
+                },
+                _ => {},
+            }
+        }
+
+        let mut queue: VecDeque<usize> = (0..edges.len()).collect();
+        let mut flips = 0;
+        while let Some(e) = queue.pop_front() {
+            if locked[e] || edge_tris[e].len() != 2 || flips > 10 * edges.len() { continue; }
+            let (t1, t2) = (edge_tris[e][0], edge_tris[e][1]);
+
+            // Write t1 as (a, b, c) and check that t2 is (b, a, d).
+            let rotations = |t: Triangle| [(t.0, t.1, t.2), (t.1, t.2, t.0), (t.2, t.0, t.1)];
+            let Edge(u, v) = edges[e];
+            let Some((a, b, c)) = rotations(triangles[t1]).into_iter().find(|(x, y, _)| (*x == u && *y == v) || (*x == v && *y == u)) else { continue };
+            let Some((_, _, d)) = rotations(triangles[t2]).into_iter().find(|(x, y, _)| *x == b && *y == a) else { continue };
+            if c == d || lookup.contains_key(&(c.min(d), c.max(d))) { continue; }
+
+            let p = |v: usize| self.vertices[v];
+            if angle(&p(a), &p(b), &p(c)) + angle(&p(a), &p(b), &p(d)) <= PI + FLIP_TOLERANCE { continue; }
+
+            // The new triangles must face the same way as the old ones.
+            let new1 = Triangle(a, d, c);
+            let new2 = Triangle(b, c, d);
+            let normal = |t: &Triangle| (p(t.1) - p(t.0)).cross(&(p(t.2) - p(t.0)));
+            let reference = normal(&triangles[t1]) + normal(&triangles[t2]);
+            if normal(&new1).dot(&reference) <= 0.0 || normal(&new2).dot(&reference) <= 0.0 { continue; }
+
+            // Edges (a, c) and (b, d) keep their triangle slot, (b, c) moves to t2 and (a, d) to t1.
+            let find = |lookup: &HashMap<(usize, usize), usize>, u: usize, v: usize| lookup[&(u.min(v), u.max(v))];
+            let (bc, ad) = (find(&lookup, b, c), find(&lookup, a, d));
+            for t in edge_tris[bc].iter_mut() { if *t == t1 { *t = t2; } }
+            for t in edge_tris[ad].iter_mut() { if *t == t2 { *t = t1; } }
+
+            lookup.remove(&(a.min(b), a.max(b)));
+            lookup.insert((c.min(d), c.max(d)), e);
+            edges[e] = Edge(c.min(d), c.max(d));
+            triangles[t1] = new1;
+            triangles[t2] = new2;
+            flipped[e] = true;
+
+            let merged: Vec<usize> = origins[t1].iter().chain(origins[t2].iter()).copied().collect();
+            origins[t1] = merged.clone();
+            origins[t2] = merged;
+
+            queue.extend([find(&lookup, a, c), bc, ad, find(&lookup, b, d)]);
+            flips += 1;
+        }
+
+        // An edge flipped back and forth ends up where it started.
+        for (e, f) in flipped.iter_mut().enumerate() { *f = *f && edges[e] != self.edges[e]; }
+        for o in origins.iter_mut() { o.sort(); o.dedup(); }
+
+        let mesh = Mesh::from_parts(self.vertices.clone(), edges, triangles, Vec::new())?;
+        Ok(Remesh {
+            original: self,
+            mesh: Rc::new(mesh),
+            flipped,
+            origins,
+        })
+    }
+
+    /// Laplacian smoothing: moves every interior vertex `iterations` times to the mean of its
+    /// neighbours. Boundary vertices stay in place, and moves that would flip a triangle over are
+    /// skipped. The connectivity does not change. Fails on volume meshes.
+    pub fn smooth(&self, iterations: usize) -> Result<Remesh<'_>, String> {
+        if !self.tetrahedra.is_empty() { return Err("only surface meshes can be smoothed".to_string()); }
+
+        let boundary: Vec<bool> = (0..self.vertices.len())
+            .map(|v| self.vertex_edges(v).iter().any(|e| self.edge_triangles(*e).len() != 2))
+            .collect();
+        let normal = |vertices: &[Vector3<f64>], t: &Triangle| (vertices[t.1] - vertices[t.0]).cross(&(vertices[t.2] - vertices[t.0]));
+
+        let mut vertices = self.vertices.clone();
+        for _ in 0..iterations {
+            for v in 0..vertices.len() {
+                let ring = self.one_ring(v);
+                if boundary[v] || ring.is_empty() { continue; }
+
+                let old = vertices[v];
+                let before: Vec<Vector3<f64>> = self.vertex_triangles(v).iter().map(|t| normal(&vertices, &self.triangles[*t])).collect();
+                vertices[v] = ring.iter().map(|u| vertices[*u]).sum::<Vector3<f64>>() / ring.len() as f64;
+                let flips = self.vertex_triangles(v).iter().zip(before.iter())
+                    .any(|(t, n)| normal(&vertices, &self.triangles[*t]).dot(n) <= 0.0);
+                if flips { vertices[v] = old; }
+            }
+        }
+
+        let mesh = Mesh::from_parts(vertices, self.edges.clone(), self.triangles.clone(), Vec::new())?;
+        Ok(Remesh {
+            original: self,
+            mesh: Rc::new(mesh),
+            flipped: vec![false; self.edges.len()],
+            origins: (0..self.triangles.len()).map(|t| vec![t]).collect(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prelude::generate::{grid, Diagonal};
+
+    /// A grid with its interior vertices moved around, so that some edges are not Delaunay.
+    fn jittered_grid() -> Mesh {
+        let mesh = grid(4.0, 4.0, 6, 6, Diagonal::Alternating);
+        let mut vertices = mesh.vertices.clone();
+        for (i, v) in vertices.iter_mut().enumerate() {
+            if v.x.abs() < 1.9 && v.z.abs() < 1.9 {
+                v.x += 0.2 * (7.0 * i as f64).sin();
+                v.z += 0.2 * (11.0 * i as f64).cos();
+            }
+        }
+        Mesh::new(vertices, mesh.triangles.clone())
+    }
+
+    #[test]
+    fn quality_of_a_grid() {
+        let stats = grid(2.0, 2.0, 2, 2, Diagonal::Forward).quality();
+        assert!((stats.min_angle - 45.0).abs() < 1e-9);
+        assert!((stats.max_angle - 90.0).abs() < 1e-9);
+        assert!((stats.max_aspect_ratio - (1.0 + 2.0_f64.sqrt()) / 3.0_f64.sqrt()).abs() < 1e-9);
+        assert_eq!((stats.min_edge_length, stats.max_edge_length), (1.0, 2.0_f64.sqrt()));
+        assert_eq!(stats.degenerate_triangles, 0);
+    }
+
+    #[test]
+    fn degenerate_and_empty_meshes_stay_finite() {
+        let mut vertices = grid(1.0, 1.0, 1, 1, Diagonal::Forward).vertices;
+        vertices.push(Vector3::new(2.0, 0.0, 0.5));
+        let mesh = Mesh::new(vertices, vec![Triangle(0, 1, 3), Triangle(2, 3, 4)]);
+        let stats = mesh.quality();
+        assert_eq!(stats.degenerate_triangles, 1);
+        assert!((stats.min_angle - 45.0).abs() < 1e-9);
+        assert!(stats.max_aspect_ratio.is_finite() && stats.mean_aspect_ratio.is_finite());
+
+        let empty = Mesh::new(Vec::new(), Vec::new()).quality();
+        for value in [empty.min_angle, empty.mean_min_angle, empty.max_aspect_ratio, empty.min_edge_length, empty.mean_edge_length, empty.edge_length_spread] {
+            assert_eq!(value, 0.0);
+        }
+        let flat = Mesh::new(mesh.vertices.clone(), vec![Triangle(2, 3, 4)]).quality();
+        assert_eq!(flat.degenerate_triangles, 1);
+        assert_eq!((flat.min_angle, flat.max_aspect_ratio), (0.0, 0.0));
+    }
+
+    /// A 2-chain of weight 2 on the left half of the mesh, and a 1-chain across it.
+    fn sample_chains(mesh: &Rc<Mesh>) -> (Chain, Chain) {
+        let mut faces = Chain::zero(2, mesh.clone());
+        for (t, tri) in mesh.triangles.iter().enumerate() {
+            if mesh.vertices[tri.0].x + mesh.vertices[tri.1].x + mesh.vertices[tri.2].x < 0.0 { faces.coeff[t] = 2.0; }
+        }
+        let path = Current { mesh: mesh.clone(), path: vec![7, 8, 9, 10, 11] }.as_chain().unwrap();
+        (faces, path)
+    }
+
+    #[test]
+    fn transfer_commutes_with_boundary() {
+        let mesh = Rc::new(jittered_grid());
+        let (faces, path) = sample_chains(&mesh);
+        assert!(mesh.quality().non_delaunay_edges > 0);
+
+        let flipped = mesh.delaunay_flip(&[&faces, &path]).unwrap();
+        assert!(flipped.flipped.iter().any(|f| *f));
+        assert!((flipped.transfer(&faces).unwrap().mass() - faces.mass()).abs() < 1e-9);
+        let smoothed = mesh.smooth(3).unwrap();
+        for remesh in [flipped, smoothed] {
+            let moved = remesh.transfer(&faces).unwrap();
+            assert_eq!(moved.boundary().unwrap().coeff, remesh.transfer(&faces.boundary().unwrap()).unwrap().coeff);
+            let moved = remesh.transfer(&path).unwrap();
+            assert_eq!(moved.boundary().unwrap().coeff, path.boundary().unwrap().coeff);
+        }
+    }
+
+    #[test]
+    fn unlocked_mesh_becomes_delaunay() {
+        let mesh = jittered_grid();
+        let remesh = mesh.delaunay_flip(&[]).unwrap();
+        assert_eq!(remesh.mesh.quality().non_delaunay_edges, 0);
+    }
+
+    #[test]
+    fn invalid_input_is_rejected() {
+        let mesh = jittered_grid();
+        let other = Rc::new(jittered_grid());
+        assert!(mesh.delaunay_flip(&[&Chain::zero(1, other)]).is_err());
+
+        let vertices = vec![Vector3::zeros(), Vector3::x(), Vector3::y(), Vector3::z()];
+        let volume = Mesh::from_tetrahedra(vertices, vec![crate::prelude::Tetrahedron(0, 1, 2, 3)]);
+        assert!(volume.delaunay_flip(&[]).is_err());
+        assert!(volume.smooth(1).is_err());
+    }
+}
@@ -0,0 +1,310 @@
+//! Serde support, enabled by the `serde` feature.
+//!
+//! Every type is stored through a plain data struct, which is also its JSON layout:
+//!
+//! - `Mesh`: `{"vertices": [[x, y, z], ...], "edges": [[a, b], ...], "triangles": [[a, b, c], ...],
+//!   "tetrahedra": [[a, b, c, d], ...]}`. Edges and triangles are stored (rather than rebuilt) so
+//!   that their order and orientation, which chain coefficients refer to, survive the round trip.
+//! - `Chain`: `{"dim": 1, "mesh": "<fingerprint>", "coeff": [...]}`, one coefficient per simplex.
+//! - `Current`: `{"mesh": "<fingerprint>", "path": [...]}`.
+//! - `MSPResult`: `{"mesh": "<fingerprint>", "alpha": [...], "mu": ..., "lambda": ...,
+//!   "median": <chain>, "decomp": [[<r_1>, <s_1>], ...]}`.
+//!
+//! Chains, currents and results do not embed their mesh. They carry its `Mesh::fingerprint` as
+//! 16 hex digits instead, and reading them back requires the mesh, which is checked against it.
+//! The compact binary form (`to_bytes`/`from_bytes`) is the same data encoded with bincode.
+
+use super::{Chain, Current, Edge, Mesh, MSPResult, Tetrahedron, Triangle};
+use nalgebra::Vector3;
+use serde::{de::DeserializeOwned, Deserialize, Deserializer, Serialize, Serializer};
+use std::rc::Rc;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MeshData {
+    pub vertices: Vec<[f64; 3]>,
+    pub edges: Vec<[usize; 2]>,
+    pub triangles: Vec<[usize; 3]>,
+    #[serde(default)]
+    pub tetrahedra: Vec<[usize; 4]>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChainData {
+    pub dim: u32,
+    pub mesh: String,
+    pub coeff: Vec<f64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CurrentData {
+    pub mesh: String,
+    pub path: Vec<usize>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MSPResultData {
+    pub mesh: String,
+    pub alpha: Vec<f64>,
+    pub mu: f64,
+    pub lambda: f64,
+    pub median: ChainData,
+    pub decomp: Vec<(ChainData, ChainData)>,
+}
+
+fn fingerprint(mesh: &Mesh) -> String {
+    format!("{:016x}", mesh.fingerprint())
+}
+
+fn check_fingerprint(mesh: &Mesh, expected: &str) -> Result<(), String> {
+    let actual = fingerprint(mesh);
+    if actual != expected { return Err(format!("mesh mismatch: expected {}, got {}", expected, actual)); }
+    Ok(())
+}
+
+fn to_json<T: Serialize>(value: &T) -> Result<String, String> {
+    serde_json::to_string(value).map_err(|e| format!("error writing JSON: {}", e))
+}
+
+fn from_json<T: DeserializeOwned>(s: &str) -> Result<T, String> {
+    serde_json::from_str(s).map_err(|e| format!("error reading JSON: {}", e))
+}
+
+fn to_bytes<T: Serialize>(value: &T) -> Result<Vec<u8>, String> {
+    bincode::serialize(value).map_err(|e| format!("error encoding: {}", e))
+}
+
+fn from_bytes<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, String> {
+    bincode::deserialize(bytes).map_err(|e| format!("error decoding: {}", e))
+}
+
+/////////////
+//  Mesh   //
+/////////////
+
+impl From<&Mesh> for MeshData {
+    fn from(mesh: &Mesh) -> Self {
+        MeshData {
+            vertices: mesh.vertices.iter().map(|v| [v.x, v.y, v.z]).collect(),
+            edges: mesh.edges.iter().map(|e| [e.0, e.1]).collect(),
+            triangles: mesh.triangles.iter().map(|t| [t.0, t.1, t.2]).collect(),
+            tetrahedra: mesh.tetrahedra.iter().map(|t| [t.0, t.1, t.2, t.3]).collect(),
+        }
+    }
+}
+
+impl TryFrom<MeshData> for Mesh {
+    type Error = String;
+
+    fn try_from(data: MeshData) -> Result<Self, Self::Error> {
+        Mesh::from_parts(
+            data.vertices.iter().map(|v| Vector3::new(v[0], v[1], v[2])).collect(),
+            data.edges.iter().map(|e| Edge(e[0], e[1])).collect(),
+            data.triangles.iter().map(|t| Triangle(t[0], t[1], t[2])).collect(),
+            data.tetrahedra.iter().map(|t| Tetrahedron(t[0], t[1], t[2], t[3])).collect(),
+        ).map_err(|e| format!("invalid mesh: {}", e))
+    }
+}
+
+impl Serialize for Mesh {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        MeshData::from(self).serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Mesh {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Mesh::try_from(MeshData::deserialize(deserializer)?).map_err(<D::Error as serde::de::Error>::custom)
+    }
+}
+
+impl Mesh {
+    pub fn to_json(&self) -> Result<String, String> { to_json(self) }
+    pub fn from_json(s: &str) -> Result<Mesh, String> { from_json(s) }
+    pub fn to_bytes(&self) -> Result<Vec<u8>, String> { to_bytes(self) }
+    pub fn from_bytes(bytes: &[u8]) -> Result<Mesh, String> { from_bytes(bytes) }
+}
+
+/////////////
+//  Chain  //
+/////////////
+
+impl From<&Chain> for ChainData {
+    fn from(chain: &Chain) -> Self {
+        ChainData {
+            dim: chain.dim,
+            mesh: fingerprint(&chain.mesh),
+            coeff: chain.coeff.clone(),
+        }
+    }
+}
+
+impl Serialize for Chain {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        ChainData::from(self).serialize(serializer)
+    }
+}
+
+impl Chain {
+    /// Rebuilds a chain on `mesh`, which must be the mesh the data was written from.
+    pub fn from_data(mesh: Rc<Mesh>, data: ChainData) -> Result<Chain, String> {
+        check_fingerprint(&mesh, &data.mesh)?;
+        if data.dim > 3 { return Err(format!("unsupported dimension {}", data.dim)); }
+        if data.coeff.len() != mesh.num_simplices(data.dim) {
+            return Err(format!("expected {} coefficients, got {}", mesh.num_simplices(data.dim), data.coeff.len()));
+        }
+
+        Ok(Chain {
+            dim: data.dim,
+            mesh,
+            coeff: data.coeff,
+        })
+    }
+
+    pub fn to_json(&self) -> Result<String, String> { to_json(self) }
+    pub fn from_json(mesh: Rc<Mesh>, s: &str) -> Result<Chain, String> { Chain::from_data(mesh, from_json(s)?) }
+    pub fn to_bytes(&self) -> Result<Vec<u8>, String> { to_bytes(self) }
+    pub fn from_bytes(mesh: Rc<Mesh>, bytes: &[u8]) -> Result<Chain, String> { Chain::from_data(mesh, from_bytes(bytes)?) }
+}
+
+/////////////
+// Current //
+/////////////
+
+impl From<&Current> for CurrentData {
+    fn from(current: &Current) -> Self {
+        CurrentData {
+            mesh: fingerprint(&current.mesh),
+            path: current.path.clone(),
+        }
+    }
+}
+
+impl Serialize for Current {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        CurrentData::from(self).serialize(serializer)
+    }
+}
+
+impl Current {
+    /// Rebuilds a current on `mesh`, which must be the mesh the data was written from.
+    pub fn from_data(mesh: Rc<Mesh>, data: CurrentData) -> Result<Current, String> {
+        check_fingerprint(&mesh, &data.mesh)?;
+        if data.path.iter().any(|v| *v >= mesh.vertices.len()) {
+            return Err("invalid current: vertex out of bounds".to_string());
+        }
+
+        Ok(Current {
+            mesh,
+            path: data.path,
+        })
+    }
+
+    pub fn to_json(&self) -> Result<String, String> { to_json(self) }
+    pub fn from_json(mesh: Rc<Mesh>, s: &str) -> Result<Current, String> { Current::from_data(mesh, from_json(s)?) }
+    pub fn to_bytes(&self) -> Result<Vec<u8>, String> { to_bytes(self) }
+    pub fn from_bytes(mesh: Rc<Mesh>, bytes: &[u8]) -> Result<Current, String> { Current::from_data(mesh, from_bytes(bytes)?) }
+}
+
+///////////////
+// MSPResult //
+///////////////
+
+impl From<&MSPResult> for MSPResultData {
+    fn from(result: &MSPResult) -> Self {
+        MSPResultData {
+            mesh: fingerprint(&result.median.mesh),
+            alpha: result.alpha.clone(),
+            mu: result.mu,
+            lambda: result.lambda,
+            median: ChainData::from(&result.median),
+            decomp: result.decomp.iter().map(|(r, s)| (ChainData::from(r), ChainData::from(s))).collect(),
+        }
+    }
+}
+
+impl Serialize for MSPResult {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        MSPResultData::from(self).serialize(serializer)
+    }
+}
+
+impl MSPResult {
+    /// Rebuilds a result on `mesh`, which must be the mesh the data was written from.
+    pub fn from_data(mesh: Rc<Mesh>, data: MSPResultData) -> Result<MSPResult, String> {
+        check_fingerprint(&mesh, &data.mesh)?;
+        if data.decomp.len() != data.alpha.len() {
+            return Err(format!("invalid result, got {} decompositions and {} weights.", data.decomp.len(), data.alpha.len()));
+        }
+
+        let mut decomp = Vec::with_capacity(data.decomp.len());
+        for (r, s) in data.decomp {
+            decomp.push((Chain::from_data(mesh.clone(), r)?, Chain::from_data(mesh.clone(), s)?));
+        }
+
+        Ok(MSPResult {
+            median: Chain::from_data(mesh, data.median)?,
+            decomp,
+            alpha: data.alpha,
+            mu: data.mu,
+            lambda: data.lambda,
+        })
+    }
+
+    pub fn to_json(&self) -> Result<String, String> { to_json(self) }
+    pub fn from_json(mesh: Rc<Mesh>, s: &str) -> Result<MSPResult, String> { MSPResult::from_data(mesh, from_json(s)?) }
+    pub fn to_bytes(&self) -> Result<Vec<u8>, String> { to_bytes(self) }
+    pub fn from_bytes(mesh: Rc<Mesh>, bytes: &[u8]) -> Result<MSPResult, String> { MSPResult::from_data(mesh, from_bytes(bytes)?) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prelude::generate::{grid, Diagonal};
+
+    fn sample() -> (Rc<Mesh>, Chain, Current) {
+        let mesh = Rc::new(grid(2.0, 2.0, 2, 2, Diagonal::Alternating));
+        let current = Current { mesh: mesh.clone(), path: vec![0, 1, 2, 5] };
+        let mut chain = Chain::zero(2, mesh.clone());
+        for (i, c) in chain.coeff.iter_mut().enumerate() { *c = i as f64 - 2.5; }
+        (mesh, chain, current)
+    }
+
+    #[test]
+    fn json_and_bytes_round_trip() {
+        let (mesh, chain, current) = sample();
+
+        let json = Mesh::from_json(&mesh.to_json().unwrap()).unwrap();
+        let bytes = Mesh::from_bytes(&mesh.to_bytes().unwrap()).unwrap();
+        for loaded in [json, bytes] {
+            assert_eq!(loaded.fingerprint(), mesh.fingerprint());
+        }
+
+        assert_eq!(Chain::from_json(mesh.clone(), &chain.to_json().unwrap()).unwrap().coeff, chain.coeff);
+        assert_eq!(Chain::from_bytes(mesh.clone(), &chain.to_bytes().unwrap()).unwrap().coeff, chain.coeff);
+        assert_eq!(Current::from_json(mesh.clone(), &current.to_json().unwrap()).unwrap().path, current.path);
+        assert_eq!(Current::from_bytes(mesh.clone(), &current.to_bytes().unwrap()).unwrap().path, current.path);
+
+        let result = MSPResult {
+            median: chain.clone(),
+            decomp: vec![(chain.clone(), -chain.clone())],
+            alpha: vec![1.0],
+            mu: 1e-3,
+            lambda: 1e-5,
+        };
+        for loaded in [
+            MSPResult::from_json(mesh.clone(), &result.to_json().unwrap()).unwrap(),
+            MSPResult::from_bytes(mesh.clone(), &result.to_bytes().unwrap()).unwrap(),
+        ] {
+            assert_eq!((loaded.alpha, loaded.mu, loaded.lambda), (vec![1.0], 1e-3, 1e-5));
+            assert_eq!(loaded.median.coeff, chain.coeff);
+            assert_eq!(loaded.decomp[0].1.coeff, (-chain.clone()).coeff);
+        }
+    }
+
+    #[test]
+    fn other_mesh_is_rejected() {
+        let (_, chain, _) = sample();
+        let other = Rc::new(grid(2.0, 2.0, 2, 2, Diagonal::Forward));
+        assert!(Chain::from_json(other, &chain.to_json().unwrap()).is_err());
+    }
+}
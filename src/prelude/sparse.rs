@@ -0,0 +1,125 @@
+/// A sparse matrix in compressed sparse row (CSR) format. Only the nonzero entries are stored:
+/// the entries of row `i` are `col_idx[row_ptr[i]..row_ptr[i+1]]` and the matching `values`,
+/// sorted by column.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SparseMatrix {
+    pub nrows: usize,
+    pub ncols: usize,
+    pub row_ptr: Vec<usize>,
+    pub col_idx: Vec<usize>,
+    pub values: Vec<f64>,
+}
+
+impl SparseMatrix {
+    /// Builds a matrix from the nonzero entries of each row, given as (column, value) pairs.
+    pub fn from_rows(ncols: usize, rows: Vec<Vec<(usize, f64)>>) -> Self {
+        let nrows = rows.len();
+        let mut row_ptr = Vec::with_capacity(nrows + 1);
+        let mut col_idx = Vec::new();
+        let mut values = Vec::new();
+
+        row_ptr.push(0);
+        for mut row in rows {
+            row.sort_by_key(|(j, _)| *j);
+            for (j, v) in row {
+                assert!(j < ncols, "column {} out of bounds.", j);
+                if v == 0.0 { continue; }
+                col_idx.push(j);
+                values.push(v);
+            }
+            row_ptr.push(col_idx.len());
+        }
+
+        SparseMatrix { nrows, ncols, row_ptr, col_idx, values }
+    }
+
+    /// Number of stored (nonzero) entries.
+    pub fn nnz(&self) -> usize {
+        self.values.len()
+    }
+
+    /// Iterates over the nonzero entries of row `i` as (column, value) pairs.
+    pub fn row(&self, i: usize) -> impl Iterator<Item = (usize, f64)> + '_ {
+        let range = self.row_ptr[i]..self.row_ptr[i+1];
+        self.col_idx[range.clone()].iter().copied().zip(self.values[range].iter().copied())
+    }
+
+    pub fn get(&self, i: usize, j: usize) -> f64 {
+        let range = self.row_ptr[i]..self.row_ptr[i+1];
+        match self.col_idx[range.clone()].binary_search(&j) {
+            Ok(k) => self.values[range.start + k],
+            Err(_) => 0.0,
+        }
+    }
+
+    /// The matrix-vector product `self * x`.
+    pub fn mul_vec(&self, x: &[f64]) -> Vec<f64> {
+        assert_eq!(x.len(), self.ncols, "dimension mismatch.");
+        (0..self.nrows).map(|i| self.row(i).map(|(j, v)| v * x[j]).sum()).collect()
+    }
+
+    pub fn transpose(&self) -> SparseMatrix {
+        let mut rows = vec![Vec::new(); self.ncols];
+        for i in 0..self.nrows {
+            for (j, v) in self.row(i) { rows[j].push((i, v)); }
+        }
+        SparseMatrix::from_rows(self.nrows, rows)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn example() -> SparseMatrix {
+        SparseMatrix::from_rows(4, vec![
+            vec![(3, 2.0), (0, 1.0)],
+            vec![],
+            vec![(1, -1.5), (2, 0.0), (3, 4.0)],
+        ])
+    }
+
+    #[test]
+    fn get_stored_and_absent() {
+        let m = example();
+        assert_eq!(m.nnz(), 4);
+        assert_eq!(m.get(0, 0), 1.0);
+        assert_eq!(m.get(0, 3), 2.0);
+        assert_eq!(m.get(2, 1), -1.5);
+        assert_eq!(m.get(0, 1), 0.0);
+        assert_eq!(m.get(1, 2), 0.0);
+        assert_eq!(m.get(2, 2), 0.0);
+        assert_eq!(m.row(0).collect::<Vec<_>>(), vec![(0, 1.0), (3, 2.0)]);
+    }
+
+    #[test]
+    fn mul_vec_matches_dense() {
+        let m = example();
+        let x = [0.5, -2.0, 3.0, 1.25];
+        let dense: Vec<f64> = (0..m.nrows)
+            .map(|i| (0..m.ncols).map(|j| m.get(i, j) * x[j]).sum())
+            .collect();
+        assert_eq!(m.mul_vec(&x), dense);
+    }
+
+    #[test]
+    fn transpose_twice_is_identity() {
+        let m = example();
+        let t = m.transpose();
+        assert_eq!((t.nrows, t.ncols), (4, 3));
+        assert_eq!(t.get(3, 2), 4.0);
+        assert_eq!(t.transpose(), m);
+    }
+
+    #[test]
+    #[should_panic(expected = "column 4 out of bounds.")]
+    fn column_out_of_bounds() {
+        SparseMatrix::from_rows(4, vec![vec![(4, 1.0)]]);
+    }
+
+    #[test]
+    #[should_panic(expected = "dimension mismatch.")]
+    fn mul_vec_length_mismatch() {
+        example().mul_vec(&[1.0, 2.0]);
+    }
+}
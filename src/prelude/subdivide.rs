@@ -0,0 +1,235 @@
+use super::{Chain, Current, Edge, Mesh, Triangle};
+use nalgebra::Vector3;
+use std::f64::consts::PI;
+use std::rc::Rc;
+
+/// A refined mesh along with how its simplices relate to the coarse one, to carry chains and
+/// currents over. See `Mesh::subdivide` and `Mesh::loop_subdivide`.
+pub struct Refinement<'a> {
+    coarse: &'a Mesh,
+    pub mesh: Rc<Mesh>,
+    /// The two halves of each coarse edge, with the sign of their orientation relative to it
+    /// (always 1, the halves follow their parent edge).
+    halves: Vec<[(usize, f64); 2]>,
+    /// The four children of each coarse triangle, with the sign of their orientation relative to
+    /// it (as used by `Mesh::boundary_operator`).
+    children: Vec<[(usize, f64); 4]>,
+}
+
+impl<'a> Refinement<'a> {
+    /// The vertex inserted on coarse edge `edge`.
+    pub fn midpoint(&self, edge: usize) -> usize {
+        self.coarse.vertices.len() + edge
+    }
+
+    /// Carries a 1- or 2-chain over to the refined mesh: the coefficient of an edge goes to both
+    /// of its halves and that of a triangle to its four children. Edges inside a coarse triangle
+    /// get zero.
+    pub fn transfer(&self, chain: &Chain) -> Result<Chain, String> {
+        if !std::ptr::eq(&*chain.mesh, self.coarse) { return Err("chain is defined on another mesh".to_string()); }
+
+        let mut fine = Chain::zero(chain.dim, self.mesh.clone());
+        match chain.dim {
+            1 => for (e, halves) in self.halves.iter().enumerate() {
+                for (idx, sign) in halves.iter() { fine.coeff[*idx] = sign * chain.coeff[e]; }
+            },
+            2 => for (t, children) in self.children.iter().enumerate() {
+                for (idx, sign) in children.iter() { fine.coeff[*idx] = sign * chain.coeff[t]; }
+            },
+            _ => return Err(format!("cannot transfer a {}-chain", chain.dim)),
+        }
+
+        Ok(fine)
+    }
+
+    /// Carries a current over to the refined mesh, passing through the midpoint of every edge.
+    pub fn transfer_current(&self, current: &Current) -> Result<Current, String> {
+        if !std::ptr::eq(&*current.mesh, self.coarse) { return Err("current is defined on another mesh".to_string()); }
+
+        let mut path = Vec::with_capacity(2 * current.path.len());
+        for i in 0..current.path.len() {
+            if i > 0 {
+                let (a, b) = (current.path[i-1], current.path[i]);
+                let (e, _) = self.coarse.find_edge(a, b).ok_or(format!("unknown edge {:?}", (a, b)))?;
+                path.push(self.midpoint(e));
+            }
+            path.push(current.path[i]);
+        }
+
+        Ok(Current {
+            mesh: self.mesh.clone(),
+            path,
+        })
+    }
+}
+
+impl Mesh {
+    /// Splits every triangle into 4 by inserting the midpoint of each edge. The vertices of the
+    /// mesh keep their indices and positions; midpoints follow, in edge order. The halves of each
+    /// edge keep its orientation, so 1-chains keep their signs. Fails on volume meshes.
+    pub fn subdivide(&self) -> Result<Refinement<'_>, String> {
+        let vertices = self.vertices.iter().copied()
+            .chain(self.edges.iter().map(|e| (self.vertices[e.0] + self.vertices[e.1]) / 2.0))
+            .collect();
+        self.refine(vertices)
+    }
+
+    /// Loop subdivision: splits every triangle into 4 like `subdivide`, then smooths the vertex
+    /// positions with Loop's masks (boundary edges and vertices use the cubic B-spline masks of
+    /// the boundary curve). The result converges to a smooth surface as it is repeated. Fails
+    /// like `subdivide`.
+    pub fn loop_subdivide(&self) -> Result<Refinement<'_>, String> {
+        let is_boundary = |e: usize| self.edge_triangles(e).len() == 1;
+        let other = |e: usize, v: usize| if self.edges[e].0 == v { self.edges[e].1 } else { self.edges[e].0 };
+
+        // Original vertices.
+        let mut vertices: Vec<Vector3<f64>> = Vec::with_capacity(self.vertices.len() + self.edges.len());
+        for (v, pos) in self.vertices.iter().enumerate() {
+            let edges = self.vertex_edges(v);
+            let boundary: Vec<usize> = edges.iter().copied().filter(|e| is_boundary(*e)).collect();
+
+            vertices.push(if boundary.len() == 2 {
+                pos * 0.75 + (self.vertices[other(boundary[0], v)] + self.vertices[other(boundary[1], v)]) * 0.125
+            } else if boundary.is_empty() && !edges.is_empty() {
+                let n = edges.len() as f64;
+                let c = 0.375 + 0.25 * (2.0 * PI / n).cos();
+                let beta = (0.625 - c * c) / n;
+                let sum: Vector3<f64> = edges.iter().map(|e| self.vertices[other(*e, v)]).sum();
+                pos * (1.0 - n * beta) + sum * beta
+            } else {
+                // Isolated and non-manifold vertices stay in place.
+                *pos
+            });
+        }
+
+        // Edge midpoints.
+        for (e, edge) in self.edges.iter().enumerate() {
+            let (a, b) = (self.vertices[edge.0], self.vertices[edge.1]);
+            let faces = self.edge_triangles(e);
+            vertices.push(if faces.len() == 2 {
+                let opposite = |t: usize| {
+                    let Triangle(i, j, k) = self.triangles[t];
+                    [i, j, k].into_iter().find(|v| *v != edge.0 && *v != edge.1).unwrap()
+                };
+                (a + b) * 0.375 + (self.vertices[opposite(faces[0])] + self.vertices[opposite(faces[1])]) * 0.125
+            } else {
+                (a + b) / 2.0
+            });
+        }
+
+        self.refine(vertices)
+    }
+
+    /// Builds the 1-to-4 split of the triangles on the given vertices (original vertices followed
+    /// by one per edge).
+    fn refine(&self, vertices: Vec<Vector3<f64>>) -> Result<Refinement<'_>, String> {
+        if !self.tetrahedra.is_empty() { return Err("only surface meshes can be subdivided".to_string()); }
+
+        let n = self.vertices.len();
+        let mid = |a: usize, b: usize| n + self.find_edge(a, b).expect("triangle edge missing from the mesh.").0;
+
+        // Halves of the coarse edges first, oriented like them, then the edges inside triangles.
+        let mut edges = Vec::with_capacity(2 * self.edges.len() + 3 * self.triangles.len());
+        for (e, edge) in self.edges.iter().enumerate() {
+            edges.push(Edge(edge.0, n + e));
+            edges.push(Edge(n + e, edge.1));
+        }
+
+        let mut triangles = Vec::with_capacity(4 * self.triangles.len());
+        for &Triangle(a, b, c) in self.triangles.iter() {
+            let (ab, bc, ca) = (mid(a, b), mid(b, c), mid(c, a));
+            edges.extend([Edge(ab, bc), Edge(bc, ca), Edge(ca, ab)]);
+            triangles.push(Triangle(a, ab, ca));
+            triangles.push(Triangle(ab, b, bc));
+            triangles.push(Triangle(ca, bc, c));
+            triangles.push(Triangle(ab, bc, ca));
+        }
+        let mesh = Mesh::from_parts(vertices, edges, triangles, Vec::new())?;
+
+        let halves = (0..self.edges.len()).map(|e| [(2*e, 1.0), (2*e + 1, 1.0)]).collect();
+        let children = self.triangles.iter().enumerate()
+            .map(|(t, tri)| [4*t, 4*t + 1, 4*t + 2, 4*t + 3].map(|idx| (idx, if mesh.triangles[idx].orientation() == tri.orientation() { 1.0 } else { -1.0 })))
+            .collect();
+
+        Ok(Refinement {
+            coarse: self,
+            mesh: Rc::new(mesh),
+            halves,
+            children,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prelude::generate::{cylinder, grid, Diagonal};
+
+    fn sample_chains(mesh: &Rc<Mesh>) -> (Chain, Chain) {
+        let mut edges = Chain::zero(1, mesh.clone());
+        let mut faces = Chain::zero(2, mesh.clone());
+        for (i, c) in edges.coeff.iter_mut().enumerate() { *c = (i % 5) as f64 - 2.0; }
+        for (i, c) in faces.coeff.iter_mut().enumerate() { *c = (i % 3) as f64 - 1.5; }
+        (edges, faces)
+    }
+
+    #[test]
+    fn transfer_commutes_with_boundary() {
+        let mut coarse = grid(2.0, 2.0, 3, 3, Diagonal::Alternating);
+        coarse.orient2d().unwrap();
+        for mesh in [Rc::new(coarse), Rc::new(cylinder(1.0, 2.0, 6, 2))] {
+            let (edges, faces) = sample_chains(&mesh);
+            for refinement in [mesh.subdivide().unwrap(), mesh.loop_subdivide().unwrap()] {
+                let fine = refinement.transfer(&faces).unwrap();
+                assert_eq!(fine.boundary().unwrap().coeff, refinement.transfer(&faces.boundary().unwrap()).unwrap().coeff);
+
+                // The boundary of a transferred 1-chain lives on the coarse vertices.
+                let fine = refinement.transfer(&edges).unwrap().boundary().unwrap();
+                let coarse = edges.boundary().unwrap();
+                assert_eq!(fine.coeff[..coarse.coeff.len()], coarse.coeff[..]);
+                assert!(fine.coeff[coarse.coeff.len()..].iter().all(|c| *c == 0.0));
+            }
+        }
+    }
+
+    #[test]
+    fn transfer_current_matches_transfer() {
+        let mesh = Rc::new(grid(2.0, 2.0, 3, 3, Diagonal::Forward));
+        let current = Current { mesh: mesh.clone(), path: vec![0, 1, 2, 6, 10, 9] };
+        let refinement = mesh.subdivide().unwrap();
+        let fine = refinement.transfer_current(&current).unwrap();
+        assert_eq!(fine.path.len(), 11);
+        assert_eq!(fine.as_chain().unwrap().coeff, refinement.transfer(&current.as_chain().unwrap()).unwrap().coeff);
+    }
+
+    #[test]
+    fn halves_follow_their_edge() {
+        let mut mesh = grid(2.0, 2.0, 2, 2, Diagonal::Forward);
+        mesh.orient2d().unwrap();
+        for e in (0..mesh.edges.len()).step_by(3) {
+            let Edge(a, b) = mesh.edges[e];
+            mesh.edges[e] = Edge(b, a);
+        }
+        let mesh = Rc::new(mesh);
+
+        let (edges, _) = sample_chains(&mesh);
+        let refinement = mesh.subdivide().unwrap();
+        let fine = refinement.transfer(&edges).unwrap();
+        for (e, edge) in mesh.edges.iter().enumerate() {
+            let m = refinement.midpoint(e);
+            for (a, b) in [(edge.0, m), (m, edge.1)] {
+                let (idx, sign) = refinement.mesh.find_edge(a, b).unwrap();
+                assert_eq!(sign, 1.0);
+                assert_eq!(fine.coeff[idx], edges.coeff[e]);
+            }
+        }
+    }
+
+    #[test]
+    fn volume_meshes_are_rejected() {
+        let vertices = vec![Vector3::zeros(), Vector3::x(), Vector3::y(), Vector3::z()];
+        let mesh = Mesh::from_tetrahedra(vertices, vec![crate::prelude::Tetrahedron(0, 1, 2, 3)]);
+        assert!(mesh.subdivide().is_err());
+        assert!(mesh.loop_subdivide().is_err());
+    }
+}
@@ -0,0 +1,183 @@
+use super::{Chain, Mesh};
+use super::vtk::escape_xml;
+use nalgebra::{Vector2, Vector3};
+use std::path::Path;
+use std::fs::File;
+use std::io::{self, prelude::*, BufWriter};
+
+const MARGIN: f64 = 10.0;
+
+/// A coordinate plane to project onto. The first named axis is drawn left to right and the second
+/// bottom to top.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AxisPlane {
+    XY,
+    XZ,
+    YZ,
+}
+
+impl AxisPlane {
+    pub fn project(&self, v: &Vector3<f64>) -> Vector2<f64> {
+        match self {
+            AxisPlane::XY => Vector2::new(v.x, v.y),
+            AxisPlane::XZ => Vector2::new(v.x, v.z),
+            AxisPlane::YZ => Vector2::new(v.y, v.z),
+        }
+    }
+
+    /// The unit normal of the plane: +z for XY, +y for XZ and +x for YZ.
+    pub fn normal(&self) -> Vector3<f64> {
+        match self {
+            AxisPlane::XY => Vector3::z(),
+            AxisPlane::XZ => Vector3::y(),
+            AxisPlane::YZ => Vector3::x(),
+        }
+    }
+}
+
+/// Draws a planar mesh with chains on top of it. 2-chains are filled with their colour, shaded by
+/// coefficient magnitude; 1-chains are drawn as lines with an arrowhead in the middle of each
+/// edge showing its orientation. Chains are drawn in the order they were added.
+pub struct SvgPlot<'a> {
+    mesh: &'a Mesh,
+    plane: AxisPlane,
+    size: f64,
+    chains: Vec<(&'a Chain, String)>,
+}
+
+impl<'a> SvgPlot<'a> {
+    pub fn new(mesh: &'a Mesh, plane: AxisPlane) -> Self {
+        SvgPlot {
+            mesh,
+            plane,
+            size: 800.0,
+            chains: Vec::new(),
+        }
+    }
+
+    /// Sets the length of the longest side of the image, in pixels.
+    pub fn size(mut self, size: f64) -> Self {
+        self.size = size;
+        self
+    }
+
+    /// Adds a chain drawn in `colour`, any SVG colour such as `red` or `#1f77b4`.
+    pub fn add_chain(mut self, chain: &'a Chain, colour: &str) -> Self {
+        self.chains.push((chain, escape_xml(colour)));
+        self
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut file = BufWriter::new(File::create(path)?);
+        self.write(&mut file)?;
+        file.flush()
+    }
+
+    pub fn write<W: Write>(&self, file: &mut W) -> io::Result<()> {
+        if self.chains.iter().any(|(chain, _)| !std::ptr::eq(&*chain.mesh, self.mesh)) {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "chain is defined on another mesh"));
+        }
+        if let Some((chain, _)) = self.chains.iter().find(|(chain, _)| chain.dim != 1 && chain.dim != 2) {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("cannot draw a {}-chain", chain.dim)));
+        }
+
+        // Fit the projected mesh into the image, flipping the vertical axis.
+        let pts: Vec<Vector2<f64>> = self.mesh.vertices.iter().map(|v| self.plane.project(v)).collect();
+        let mut min = Vector2::new(f64::INFINITY, f64::INFINITY);
+        let mut max = Vector2::new(f64::NEG_INFINITY, f64::NEG_INFINITY);
+        for p in pts.iter() {
+            min = min.inf(p);
+            max = max.sup(p);
+        }
+        if pts.is_empty() { min = Vector2::zeros(); max = Vector2::zeros(); }
+
+        let extent = (max - min).max().max(f64::EPSILON);
+        let scale = (self.size - 2.0 * MARGIN) / extent;
+        let width = (max.x - min.x) * scale + 2.0 * MARGIN;
+        let height = (max.y - min.y) * scale + 2.0 * MARGIN;
+        let pts: Vec<Vector2<f64>> = pts.iter()
+            .map(|p| Vector2::new(MARGIN + (p.x - min.x) * scale, height - MARGIN - (p.y - min.y) * scale))
+            .collect();
+
+        // Strokes are sized relative to the average edge length on screen.
+        let avg_edge = if self.mesh.edges.is_empty() { 1.0 } else {
+            self.mesh.edges.iter().map(|e| (pts[e.0] - pts[e.1]).norm()).sum::<f64>() / self.mesh.edges.len() as f64
+        };
+        let stroke = (avg_edge / 8.0).clamp(0.5, 4.0);
+
+        writeln!(file, "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{:.0}\" height=\"{:.0}\" viewBox=\"0 0 {:.2} {:.2}\">", width.ceil(), height.ceil(), width, height)?;
+        writeln!(file, "<defs>")?;
+        for (i, (_, colour)) in self.chains.iter().enumerate() {
+            writeln!(file, "<marker id=\"arrow{}\" viewBox=\"0 0 10 10\" refX=\"5\" refY=\"5\" markerWidth=\"4\" markerHeight=\"4\" orient=\"auto\"><path d=\"M 0 0 L 10 5 L 0 10 z\" fill=\"{}\"/></marker>", i, colour)?;
+        }
+        writeln!(file, "</defs>")?;
+        writeln!(file, "<rect width=\"100%\" height=\"100%\" fill=\"white\"/>")?;
+
+        // The mesh itself.
+        write!(file, "<path fill=\"none\" stroke=\"#c0c0c0\" stroke-width=\"{:.2}\" d=\"", stroke / 4.0)?;
+        for e in self.mesh.edges.iter() {
+            write!(file, "M {:.2} {:.2} L {:.2} {:.2} ", pts[e.0].x, pts[e.0].y, pts[e.1].x, pts[e.1].y)?;
+        }
+        writeln!(file, "\"/>")?;
+
+        for (i, (chain, colour)) in self.chains.iter().enumerate() {
+            let max_coeff = chain.coeff.iter().fold(0.0_f64, |m, c| m.max(c.abs()));
+            if max_coeff == 0.0 { continue; }
+
+            writeln!(file, "<g id=\"chain{}\">", i)?;
+            match chain.dim {
+                1 => {
+                    for (idx, c) in chain.coeff.iter().enumerate() {
+                        if *c == 0.0 { continue; }
+                        let e = chain.mesh.edges[idx];
+                        let (a, b) = if *c > 0.0 { (pts[e.0], pts[e.1]) } else { (pts[e.1], pts[e.0]) };
+                        let mid = (a + b) / 2.0;
+                        let w = stroke * (0.5 + 0.5 * c.abs() / max_coeff);
+                        writeln!(file, "<path d=\"M {:.2} {:.2} L {:.2} {:.2} L {:.2} {:.2}\" fill=\"none\" stroke=\"{}\" stroke-width=\"{:.2}\" stroke-linecap=\"round\" marker-mid=\"url(#arrow{})\"/>",
+                            a.x, a.y, mid.x, mid.y, b.x, b.y, colour, w, i)?;
+                    }
+                }
+                _ => {
+                    for (idx, c) in chain.coeff.iter().enumerate() {
+                        if *c == 0.0 { continue; }
+                        let t = chain.mesh.triangles[idx];
+                        let (a, b, c2) = (pts[t.0], pts[t.1], pts[t.2]);
+                        writeln!(file, "<path d=\"M {:.2} {:.2} L {:.2} {:.2} L {:.2} {:.2} z\" fill=\"{}\" fill-opacity=\"{:.3}\" stroke=\"none\"/>",
+                            a.x, a.y, b.x, b.y, c2.x, c2.y, colour, 0.7 * c.abs() / max_coeff)?;
+                    }
+                }
+            }
+            writeln!(file, "</g>")?;
+        }
+
+        writeln!(file, "</svg>")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prelude::generate::{grid, Diagonal};
+    use std::rc::Rc;
+
+    #[test]
+    fn colours_are_escaped() {
+        let mesh = Rc::new(grid(1.0, 1.0, 2, 2, Diagonal::Forward));
+        let mut chain = Chain::zero(2, mesh.clone());
+        chain.coeff[0] = 1.0;
+
+        let mut out = Vec::new();
+        SvgPlot::new(&mesh, AxisPlane::XZ).add_chain(&chain, "red\" onload=\"x").write(&mut out).unwrap();
+        let svg = String::from_utf8(out).unwrap();
+        assert!(svg.contains("fill=\"red&quot; onload=&quot;x\""));
+        assert!(!svg.contains("onload=\""));
+    }
+
+    #[test]
+    fn other_dimensions_are_rejected() {
+        let mesh = Rc::new(grid(1.0, 1.0, 1, 1, Diagonal::Forward));
+        let chain = Chain::zero(0, mesh.clone());
+        let result = SvgPlot::new(&mesh, AxisPlane::XZ).add_chain(&chain, "red").write(&mut Vec::new());
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::InvalidInput);
+    }
+}
@@ -0,0 +1,118 @@
+use super::{Mesh, Tetrahedron};
+use nalgebra::Vector3;
+use std::path::Path;
+use std::fs::File;
+use std::io::{prelude::*, BufReader};
+
+/// Reads the non-empty lines of a TetGen file, with comments removed, as (line number, tokens).
+fn read_lines(path: &Path) -> Result<Vec<(usize, Vec<String>)>, String> {
+    let file = File::open(path).map_err(|e| format!("error opening file {}: {}", path.display(), e))?;
+
+    let mut lines = Vec::new();
+    let reader = BufReader::new(file);
+    for (lineno, line) in reader.lines().enumerate() {
+        let line = line.map_err(|e| format!("error reading file: {}", e))?;
+        let text = match line.find('#') {
+            Some(idx) => &line[..idx],
+            None => &line[..],
+        };
+        let parts: Vec<String> = text.split_whitespace().map(|s| s.to_string()).collect();
+        if !parts.is_empty() { lines.push((lineno+1, parts)); }
+    }
+
+    Ok(lines)
+}
+
+fn parse<T: std::str::FromStr>(s: &str, lineno: usize, what: &str) -> Result<T, String> {
+    s.parse::<T>().map_err(|_| format!("line {}: invalid {} '{}'", lineno, what, s))
+}
+
+impl Mesh {
+    /// Loads a TetGen volume mesh from its `.node` and `.ele` files. `path` may name either file
+    /// (or neither extension); the other one is found by swapping the extension. Both 0- and
+    /// 1-based numbering are accepted, following the first index of the `.node` file.
+    pub fn load_tetgen<P: AsRef<Path>>(path: P) -> Result<Mesh, String> {
+        let nodes = read_lines(&path.as_ref().with_extension("node"))?;
+        let elements = read_lines(&path.as_ref().with_extension("ele"))?;
+
+        // Nodes.
+        let (lineno, header) = nodes.first().ok_or(".node file is empty".to_string())?;
+        let num_nodes: usize = parse(&header[0], *lineno, "number of points")?;
+        if header.get(1).map(|s| s.as_str()).unwrap_or("3") != "3" {
+            return Err(format!("line {}: only 3-dimensional .node files are supported", lineno));
+        }
+        if nodes.len() < num_nodes + 1 {
+            return Err(format!("expected {} points, found {}", num_nodes, nodes.len() - 1));
+        }
+
+        let mut base = 0;
+        let mut vertices = Vec::with_capacity(num_nodes);
+        for (i, (lineno, parts)) in nodes[1..num_nodes+1].iter().enumerate() {
+            if parts.len() < 4 { return Err(format!("line {}: point needs an index and 3 coordinates", lineno)); }
+            let idx: usize = parse(&parts[0], *lineno, "point index")?;
+            if i == 0 { base = idx; }
+            if idx != base + i { return Err(format!("line {}: points are not numbered consecutively", lineno)); }
+
+            let x = parse(&parts[1], *lineno, "'x' coordinate")?;
+            let y = parse(&parts[2], *lineno, "'y' coordinate")?;
+            let z = parse(&parts[3], *lineno, "'z' coordinate")?;
+            vertices.push(Vector3::new(x, y, z));
+        }
+
+        // Tetrahedra. Only the 4 corners of quadratic (10-node) tetrahedra are used.
+        let (lineno, header) = elements.first().ok_or(".ele file is empty".to_string())?;
+        let num_tets: usize = parse(&header[0], *lineno, "number of tetrahedra")?;
+        if elements.len() < num_tets + 1 {
+            return Err(format!("expected {} tetrahedra, found {}", num_tets, elements.len() - 1));
+        }
+
+        let mut tetrahedra = Vec::with_capacity(num_tets);
+        for (lineno, parts) in elements[1..num_tets+1].iter() {
+            if parts.len() < 5 { return Err(format!("line {}: tetrahedron needs an index and 4 points", lineno)); }
+
+            let mut v = [0; 4];
+            for k in 0..4 {
+                let idx: usize = parse(&parts[k+1], *lineno, "point index")?;
+                if idx < base || idx - base >= vertices.len() { return Err(format!("line {}: point index {} out of bounds", lineno, idx)); }
+                v[k] = idx - base;
+            }
+            tetrahedra.push(Tetrahedron(v[0], v[1], v[2], v[3]));
+        }
+
+        Ok(Mesh::from_tetrahedra(vertices, tetrahedra))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn one_based_node_and_ele() {
+        let base = std::env::temp_dir().join(format!("msp-tetgen-{}", std::process::id()));
+        std::fs::write(base.with_extension("node"), "# two tetrahedra sharing a face\n5 3 0 0\n\
+            1 0 0 0\n2 1 0 0\n3 0 1 0\n4 0 0 1\n5 0 0 -1\n").unwrap();
+        std::fs::write(base.with_extension("ele"), "2 4 0\n1 1 2 3 4\n2 1 3 2 5\n").unwrap();
+        let mesh = Mesh::load_tetgen(base.with_extension("ele"));
+        std::fs::remove_file(base.with_extension("node")).unwrap();
+        std::fs::remove_file(base.with_extension("ele")).unwrap();
+
+        let mesh = mesh.unwrap();
+        assert_eq!(mesh.vertices.len(), 5);
+        assert_eq!(mesh.tetrahedra, vec![Tetrahedron(0, 1, 2, 3), Tetrahedron(0, 2, 1, 4)]);
+        assert_eq!(mesh.triangles.len(), 7);
+        assert_eq!(mesh.edges.len(), 9);
+    }
+
+    #[test]
+    fn out_of_bounds_index() {
+        let base = std::env::temp_dir().join(format!("msp-tetgen-bad-{}", std::process::id()));
+        std::fs::write(base.with_extension("node"), "4 3 0 0\n0 0 0 0\n1 1 0 0\n2 0 1 0\n3 0 0 1\n").unwrap();
+        std::fs::write(base.with_extension("ele"), "1 4 0\n0 0 1 2 4\n").unwrap();
+        let mesh = Mesh::load_tetgen(&base);
+        std::fs::remove_file(base.with_extension("node")).unwrap();
+        std::fs::remove_file(base.with_extension("ele")).unwrap();
+
+        assert_eq!(mesh.err(), Some("line 2: point index 4 out of bounds".to_string()));
+    }
+}
@@ -0,0 +1,286 @@
+use super::{Edge, Mesh};
+use std::collections::HashMap;
+use std::fmt;
+
+/// Triangles whose area is below this fraction of their longest side squared are degenerate.
+pub(super) const DEGENERATE_AREA: f64 = 1e-12;
+
+/// Topology and quality report of a mesh, see `Mesh::validate`.
+///
+/// For volume meshes the surface checks (manifoldness, boundary loops, Euler characteristic and
+/// genus) are done on the boundary surface, i.e. on the triangles belonging to one tetrahedron.
+#[derive(Debug, Clone, Default)]
+pub struct MeshReport {
+    pub num_vertices: usize,
+    pub num_edges: usize,
+    pub num_triangles: usize,
+    pub num_tetrahedra: usize,
+    /// Edges shared by more than two triangles.
+    pub non_manifold_edges: Vec<usize>,
+    /// Vertices whose triangles do not form a single fan (such as the tip of two cones).
+    pub non_manifold_vertices: Vec<usize>,
+    /// Closed paths of boundary edges, as lists of vertices.
+    pub boundary_loops: Vec<Vec<usize>>,
+    pub components: usize,
+    pub euler_characteristic: i64,
+    /// Genus of the surface, assuming it is orientable. `None` if the surface is not a manifold.
+    pub genus: Option<usize>,
+    /// Triangles with a repeated vertex or (nearly) zero area.
+    pub degenerate_triangles: Vec<usize>,
+    /// Pairs of vertices at the same position, as (first occurrence, duplicate).
+    pub duplicate_vertices: Vec<(usize, usize)>,
+    /// Vertices not used by any simplex.
+    pub unreferenced_vertices: Vec<usize>,
+    /// Pairs of triangles with the same vertices, as (first occurrence, duplicate).
+    pub duplicate_triangles: Vec<(usize, usize)>,
+}
+
+impl MeshReport {
+    /// Whether the mesh is free of problems. Boundaries and multiple components are allowed.
+    pub fn is_valid(&self) -> bool {
+        self.non_manifold_edges.is_empty()
+            && self.non_manifold_vertices.is_empty()
+            && self.degenerate_triangles.is_empty()
+            && self.duplicate_vertices.is_empty()
+            && self.unreferenced_vertices.is_empty()
+            && self.duplicate_triangles.is_empty()
+    }
+}
+
+/// Writes up to 10 items of a list, followed by the number of items left out.
+fn write_list<T: fmt::Debug>(f: &mut fmt::Formatter, name: &str, items: &[T]) -> fmt::Result {
+    if items.is_empty() { return Ok(()); }
+    write!(f, "  {}:", name)?;
+    for item in items.iter().take(10) { write!(f, " {:?}", item)?; }
+    if items.len() > 10 { write!(f, " ... ({} more)", items.len() - 10)?; }
+    writeln!(f)
+}
+
+impl fmt::Display for MeshReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "vertices: {} ({} unreferenced, {} duplicate)", self.num_vertices, self.unreferenced_vertices.len(), self.duplicate_vertices.len())?;
+        writeln!(f, "edges: {} ({} non-manifold)", self.num_edges, self.non_manifold_edges.len())?;
+        writeln!(f, "triangles: {} ({} degenerate, {} duplicate)", self.num_triangles, self.degenerate_triangles.len(), self.duplicate_triangles.len())?;
+        if self.num_tetrahedra > 0 { writeln!(f, "tetrahedra: {}", self.num_tetrahedra)?; }
+        writeln!(f, "non-manifold vertices: {}", self.non_manifold_vertices.len())?;
+        writeln!(f, "connected components: {}", self.components)?;
+        writeln!(f, "boundary loops: {}", self.boundary_loops.len())?;
+        writeln!(f, "euler characteristic: {}", self.euler_characteristic)?;
+        match self.genus {
+            Some(g) => writeln!(f, "genus: {}", g)?,
+            None => writeln!(f, "genus: undefined (not a manifold)")?,
+        }
+
+        write_list(f, "non-manifold edges", &self.non_manifold_edges)?;
+        write_list(f, "non-manifold vertices", &self.non_manifold_vertices)?;
+        write_list(f, "degenerate triangles", &self.degenerate_triangles)?;
+        write_list(f, "duplicate vertices", &self.duplicate_vertices)?;
+        write_list(f, "unreferenced vertices", &self.unreferenced_vertices)?;
+        write_list(f, "duplicate triangles", &self.duplicate_triangles)?;
+        for (i, l) in self.boundary_loops.iter().enumerate().take(10) {
+            write_list(f, &format!("boundary loop {} ({} edges)", i+1, l.len()), l)?;
+        }
+        if self.boundary_loops.len() > 10 { writeln!(f, "  ... ({} more boundary loops)", self.boundary_loops.len() - 10)?; }
+
+        write!(f, "{}", if self.is_valid() { "mesh is valid" } else { "mesh has problems" })
+    }
+}
+
+/// Union-find over `n` elements.
+pub(super) struct Components {
+    parent: Vec<usize>,
+}
+
+impl Components {
+    pub(super) fn new(n: usize) -> Self {
+        Components { parent: (0..n).collect() }
+    }
+
+    pub(super) fn find(&mut self, mut x: usize) -> usize {
+        while self.parent[x] != x {
+            self.parent[x] = self.parent[self.parent[x]];
+            x = self.parent[x];
+        }
+        x
+    }
+
+    pub(super) fn union(&mut self, a: usize, b: usize) {
+        let (a, b) = (self.find(a), self.find(b));
+        if a != b { self.parent[a.max(b)] = a.min(b); }
+    }
+}
+
+impl Mesh {
+    /// Checks the topology and quality of the mesh, see `MeshReport`.
+    pub fn validate(&self) -> MeshReport {
+        let mut report = MeshReport {
+            num_vertices: self.vertices.len(),
+            num_edges: self.edges.len(),
+            num_triangles: self.triangles.len(),
+            num_tetrahedra: self.tetrahedra.len(),
+            ..Default::default()
+        };
+
+        // The surface being checked.
+        let surface: Vec<usize> = if self.tetrahedra.is_empty() { (0..self.triangles.len()).collect() }
+            else { self.boundary_triangles() };
+        let mut on_surface = vec![false; self.triangles.len()];
+        for t in surface.iter() { on_surface[*t] = true; }
+        let edge_faces = |e: usize| self.edge_triangles(e).iter().copied().filter(|t| on_surface[*t]).collect::<Vec<usize>>();
+
+        // Edges.
+        let mut surface_edges = Vec::new();
+        let mut boundary = Vec::new();
+        for e in 0..self.edges.len() {
+            match edge_faces(e).len() {
+                0 => continue,
+                1 => boundary.push(e),
+                2 => {},
+                _ => report.non_manifold_edges.push(e),
+            }
+            surface_edges.push(e);
+        }
+
+        // Vertices: the triangles around a manifold vertex are connected through its edges.
+        let mut surface_vertices = 0;
+        for v in 0..self.vertices.len() {
+            let fan: Vec<usize> = self.vertex_triangles(v).iter().copied().filter(|t| on_surface[*t]).collect();
+            if fan.is_empty() { continue; }
+            surface_vertices += 1;
+
+            let mut components = Components::new(fan.len());
+            for e in self.vertex_edges(v).iter() {
+                let faces = edge_faces(*e);
+                for pair in faces.windows(2) {
+                    let i = fan.iter().position(|t| *t == pair[0]).unwrap();
+                    let j = fan.iter().position(|t| *t == pair[1]).unwrap();
+                    components.union(i, j);
+                }
+            }
+            if (0..fan.len()).any(|i| components.find(i) != 0) { report.non_manifold_vertices.push(v); }
+        }
+
+        // Boundary loops, walking unused boundary edges until getting back to the start.
+        let mut at_vertex: HashMap<usize, Vec<usize>> = HashMap::new();
+        for e in boundary.iter() {
+            at_vertex.entry(self.edges[*e].0).or_default().push(*e);
+            at_vertex.entry(self.edges[*e].1).or_default().push(*e);
+        }
+        let mut used = vec![false; self.edges.len()];
+        for &e in boundary.iter() {
+            if used[e] { continue; }
+            used[e] = true;
+            let Edge(start, mut cur) = self.edges[e];
+            let mut path = vec![start];
+            while cur != start {
+                path.push(cur);
+                let Some(next) = at_vertex[&cur].iter().copied().find(|f| !used[*f]) else { break };
+                used[next] = true;
+                cur = if self.edges[next].0 == cur { self.edges[next].1 } else { self.edges[next].0 };
+            }
+            report.boundary_loops.push(path);
+        }
+
+        // Connected components, Euler characteristic and genus.
+        let mut components = Components::new(self.vertices.len());
+        for e in surface_edges.iter() { components.union(self.edges[*e].0, self.edges[*e].1); }
+        let mut roots: Vec<usize> = surface.iter().map(|t| components.find(self.triangles[*t].0)).collect();
+        roots.sort();
+        roots.dedup();
+        report.components = roots.len();
+        report.euler_characteristic = surface_vertices as i64 - surface_edges.len() as i64 + surface.len() as i64;
+
+        let manifold = report.non_manifold_edges.is_empty() && report.non_manifold_vertices.is_empty();
+        let twice_genus = 2 * report.components as i64 - report.boundary_loops.len() as i64 - report.euler_characteristic;
+        if manifold && twice_genus >= 0 && twice_genus % 2 == 0 { report.genus = Some((twice_genus / 2) as usize); }
+
+        // Degenerate and duplicate triangles.
+        let mut seen = HashMap::new();
+        for (i, t) in self.triangles.iter().enumerate() {
+            let longest = [Edge(t.0, t.1), Edge(t.1, t.2), Edge(t.2, t.0)].iter().map(|e| e.length(self)).fold(0.0, f64::max);
+            if t.0 == t.1 || t.1 == t.2 || t.2 == t.0 || t.area(self) <= DEGENERATE_AREA * longest * longest {
+                report.degenerate_triangles.push(i);
+            }
+
+            let mut key = [t.0, t.1, t.2];
+            key.sort();
+            if let Some(first) = seen.insert(key, i) {
+                report.duplicate_triangles.push((first, i));
+                seen.insert(key, first);
+            }
+        }
+
+        // Duplicate and unreferenced vertices.
+        let mut seen = HashMap::new();
+        for (i, v) in self.vertices.iter().enumerate() {
+            // Adding 0.0 turns -0.0 into 0.0.
+            let key = [(v.x + 0.0).to_bits(), (v.y + 0.0).to_bits(), (v.z + 0.0).to_bits()];
+            if let Some(first) = seen.get(&key) { report.duplicate_vertices.push((*first, i)); }
+            else { seen.insert(key, i); }
+        }
+
+        let mut referenced = vec![false; self.vertices.len()];
+        for e in self.edges.iter() { referenced[e.0] = true; referenced[e.1] = true; }
+        report.unreferenced_vertices = (0..self.vertices.len()).filter(|v| !referenced[*v]).collect();
+
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prelude::generate::{grid, torus, Diagonal};
+    use crate::prelude::Triangle;
+    use nalgebra::Vector3;
+
+    #[test]
+    fn torus_has_genus_one() {
+        let report = torus(2.0, 0.5, 8, 5).validate();
+        assert_eq!(report.euler_characteristic, 0);
+        assert_eq!(report.genus, Some(1));
+        assert_eq!(report.components, 1);
+        assert!(report.boundary_loops.is_empty());
+        assert!(report.is_valid());
+    }
+
+    #[test]
+    fn grid_has_one_boundary_loop() {
+        let report = grid(3.0, 3.0, 3, 3, Diagonal::Alternating).validate();
+        assert_eq!(report.boundary_loops.len(), 1);
+        assert_eq!(report.boundary_loops[0].len(), 12);
+        assert_eq!(report.euler_characteristic, 1);
+        assert_eq!(report.genus, Some(0));
+        assert!(report.is_valid());
+    }
+
+    #[test]
+    fn defects_are_reported() {
+        let vertices = vec![
+            // Three triangles around the edge (0, 1).
+            Vector3::new(0.0, 0.0, 0.0), Vector3::new(1.0, 0.0, 0.0), Vector3::new(0.0, 1.0, 0.0),
+            Vector3::new(0.0, 0.0, 1.0), Vector3::new(0.0, -1.0, 0.0),
+            // A triangle given twice.
+            Vector3::new(5.0, 0.0, 0.0), Vector3::new(6.0, 0.0, 0.0), Vector3::new(5.0, 1.0, 0.0),
+            // A flat triangle.
+            Vector3::new(10.0, 0.0, 0.0), Vector3::new(11.0, 0.0, 0.0), Vector3::new(12.0, 0.0, 0.0),
+            // An unused vertex.
+            Vector3::new(20.0, 0.0, 0.0),
+        ];
+        let triangles = vec![
+            Triangle(0, 1, 2), Triangle(0, 1, 3), Triangle(0, 1, 4),
+            Triangle(5, 6, 7), Triangle(7, 6, 5),
+            Triangle(8, 9, 10),
+        ];
+        let mesh = Mesh::new(vertices, triangles);
+        let report = mesh.validate();
+
+        assert!(report.non_manifold_edges.contains(&mesh.find_edge(0, 1).unwrap().0));
+        assert_eq!(report.duplicate_triangles, vec![(3, 4)]);
+        assert_eq!(report.degenerate_triangles, vec![5]);
+        assert_eq!(report.unreferenced_vertices, vec![11]);
+        assert!(report.duplicate_vertices.is_empty());
+        assert_eq!(report.genus, None);
+        assert!(!report.is_valid());
+    }
+}
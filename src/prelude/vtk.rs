@@ -0,0 +1,141 @@
+use super::{Chain, Mesh};
+use std::path::Path;
+use std::fs::File;
+use std::io::{self, prelude::*, BufWriter};
+
+const VTK_LINE: u8 = 3;
+const VTK_TRIANGLE: u8 = 5;
+const VTK_TETRA: u8 = 10;
+
+impl Mesh {
+    /// Saves the mesh as a VTK unstructured grid (`.vtu`) for ParaView. The cells are the edges
+    /// (VTK lines), then the triangles, then the tetrahedra, with a `dimension` cell array to tell
+    /// them apart. Each chain becomes a cell array holding its coefficients on the cells of its
+    /// dimension, relative to the cells as written, and 0 on the others (rather than NaN, which
+    /// not every reader parses); filter on `dimension` to tell them apart from zero coefficients.
+    pub fn save_vtu<P: AsRef<Path>>(&self, path: P, chains: &[(&str, &Chain)]) -> io::Result<()> {
+        for (name, chain) in chains.iter() {
+            if !std::ptr::eq(&*chain.mesh, self) {
+                return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("chain '{}' is defined on another mesh", name)));
+            }
+        }
+
+        let m = self.edges.len();
+        let n = self.triangles.len();
+        let k = self.tetrahedra.len();
+
+        let mut file = BufWriter::new(File::create(path)?);
+        writeln!(file, "<?xml version=\"1.0\"?>")?;
+        writeln!(file, "<VTKFile type=\"UnstructuredGrid\" version=\"0.1\" byte_order=\"LittleEndian\">")?;
+        writeln!(file, "  <UnstructuredGrid>")?;
+        writeln!(file, "    <Piece NumberOfPoints=\"{}\" NumberOfCells=\"{}\">", self.vertices.len(), m + n + k)?;
+
+        writeln!(file, "      <Points>")?;
+        writeln!(file, "        <DataArray type=\"Float64\" NumberOfComponents=\"3\" format=\"ascii\">")?;
+        for v in self.vertices.iter() {
+            writeln!(file, "          {} {} {}", v.x, v.y, v.z)?;
+        }
+        writeln!(file, "        </DataArray>")?;
+        writeln!(file, "      </Points>")?;
+
+        writeln!(file, "      <Cells>")?;
+        writeln!(file, "        <DataArray type=\"Int64\" Name=\"connectivity\" format=\"ascii\">")?;
+        for e in self.edges.iter() {
+            writeln!(file, "          {} {}", e.0, e.1)?;
+        }
+        for t in self.triangles.iter() {
+            writeln!(file, "          {} {} {}", t.0, t.1, t.2)?;
+        }
+        for t in self.tetrahedra.iter() {
+            writeln!(file, "          {} {} {} {}", t.0, t.1, t.2, t.3)?;
+        }
+        writeln!(file, "        </DataArray>")?;
+        writeln!(file, "        <DataArray type=\"Int64\" Name=\"offsets\" format=\"ascii\">")?;
+        for i in 0..m {
+            writeln!(file, "          {}", 2*(i+1))?;
+        }
+        for j in 0..n {
+            writeln!(file, "          {}", 2*m + 3*(j+1))?;
+        }
+        for l in 0..k {
+            writeln!(file, "          {}", 2*m + 3*n + 4*(l+1))?;
+        }
+        writeln!(file, "        </DataArray>")?;
+        writeln!(file, "        <DataArray type=\"UInt8\" Name=\"types\" format=\"ascii\">")?;
+        for _ in 0..m { writeln!(file, "          {}", VTK_LINE)?; }
+        for _ in 0..n { writeln!(file, "          {}", VTK_TRIANGLE)?; }
+        for _ in 0..k { writeln!(file, "          {}", VTK_TETRA)?; }
+        writeln!(file, "        </DataArray>")?;
+        writeln!(file, "      </Cells>")?;
+
+        writeln!(file, "      <CellData>")?;
+        writeln!(file, "        <DataArray type=\"UInt8\" Name=\"dimension\" format=\"ascii\">")?;
+        for _ in 0..m { writeln!(file, "          1")?; }
+        for _ in 0..n { writeln!(file, "          2")?; }
+        for _ in 0..k { writeln!(file, "          3")?; }
+        writeln!(file, "        </DataArray>")?;
+        for (name, chain) in chains.iter() {
+            writeln!(file, "        <DataArray type=\"Float64\" Name=\"{}\" format=\"ascii\">", escape_xml(name))?;
+            for (dim, count) in [(1, m), (2, n), (3, k)] {
+                for i in 0..count {
+                    if chain.dim == dim { writeln!(file, "          {}", chain.coeff[i] * chain.stored_sign(i))?; }
+                    else { writeln!(file, "          0")?; }
+                }
+            }
+            writeln!(file, "        </DataArray>")?;
+        }
+        writeln!(file, "      </CellData>")?;
+
+        writeln!(file, "    </Piece>")?;
+        writeln!(file, "  </UnstructuredGrid>")?;
+        writeln!(file, "</VTKFile>")?;
+        file.flush()
+    }
+}
+
+/// Escapes the characters that cannot appear as is in XML attributes and text.
+pub(super) fn escape_xml(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&apos;"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prelude::generate::{grid, Diagonal};
+    use std::rc::Rc;
+
+    #[test]
+    fn triangle_values_follow_written_orientation() {
+        let mesh = Rc::new(grid(2.0, 2.0, 3, 3, Diagonal::Alternating));
+        let mut chain = Chain::zero(2, mesh.clone());
+        for c in chain.coeff.iter_mut() { *c = 1.0; }
+
+        let path = std::env::temp_dir().join(format!("msp-vtk-{}.vtu", std::process::id()));
+        mesh.save_vtu(&path, &[("a<b & \"c\"", &chain)]).unwrap();
+        let contents = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(contents.contains("Name=\"a&lt;b &amp; &quot;c&quot;\""));
+        assert!(!contents.contains("NaN"));
+        let array = contents.split("Name=\"a&lt;b").nth(1).unwrap();
+        let values: Vec<f64> = array.lines().skip(1)
+            .take(mesh.edges.len() + mesh.triangles.len())
+            .map(|l| l.trim().parse().unwrap())
+            .collect();
+        assert!(values[..mesh.edges.len()].iter().all(|v| *v == 0.0));
+        for (i, v) in values[mesh.edges.len()..].iter().enumerate() {
+            assert_eq!(*v, chain.stored_sign(i));
+        }
+    }
+}